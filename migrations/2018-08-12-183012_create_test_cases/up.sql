@@ -0,0 +1,9 @@
+CREATE TABLE test_case (
+    id INTEGER PRIMARY KEY NOT NULL,
+    pid INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    input TEXT NOT NULL,
+    output TEXT NOT NULL,
+    sample BOOLEAN NOT NULL,
+    FOREIGN KEY(pid) REFERENCES problem(id)
+);
@@ -0,0 +1,16 @@
+CREATE TABLE solution_old (
+    id INTEGER PRIMARY KEY NOT NULL,
+    pid INTEGER NOT NULL,
+    uid INTEGER NOT NULL,
+    language INTEGER NOT NULL,
+    code TEXT NOT NULL,
+    FOREIGN KEY(pid) REFERENCES problem(id),
+    FOREIGN KEY(uid) REFERENCES user(id),
+    FOREIGN KEY(language) REFERENCES language(id)
+);
+
+INSERT INTO solution_old (id, pid, uid, language, code)
+    SELECT id, pid, uid, language, code FROM solution;
+
+DROP TABLE solution;
+ALTER TABLE solution_old RENAME TO solution;
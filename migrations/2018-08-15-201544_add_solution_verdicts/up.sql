@@ -0,0 +1,23 @@
+CREATE TABLE solution_new (
+    id INTEGER PRIMARY KEY NOT NULL,
+    pid INTEGER NOT NULL,
+    uid INTEGER NOT NULL,
+    language INTEGER NOT NULL,
+    code TEXT NOT NULL,
+    verdict TEXT NOT NULL DEFAULT 'PD',
+    score INTEGER NOT NULL DEFAULT 0,
+    runtime INTEGER,
+    memory INTEGER,
+    message TEXT NOT NULL DEFAULT '',
+    submitted_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    judged_at TIMESTAMP,
+    FOREIGN KEY(pid) REFERENCES problem(id),
+    FOREIGN KEY(uid) REFERENCES user(id),
+    FOREIGN KEY(language) REFERENCES language(id)
+);
+
+INSERT INTO solution_new (id, pid, uid, language, code)
+    SELECT id, pid, uid, language, code FROM solution;
+
+DROP TABLE solution;
+ALTER TABLE solution_new RENAME TO solution;
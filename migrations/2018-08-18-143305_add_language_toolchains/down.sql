@@ -0,0 +1,9 @@
+CREATE TABLE language_old (
+    id INTEGER PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL
+);
+
+INSERT INTO language_old (id, name) SELECT id, name FROM language;
+
+DROP TABLE language;
+ALTER TABLE language_old RENAME TO language;
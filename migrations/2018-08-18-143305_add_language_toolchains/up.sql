@@ -0,0 +1,18 @@
+ALTER TABLE language ADD COLUMN source TEXT NOT NULL DEFAULT '';
+ALTER TABLE language ADD COLUMN compile TEXT NOT NULL DEFAULT '';
+ALTER TABLE language ADD COLUMN run TEXT NOT NULL DEFAULT '';
+ALTER TABLE language ADD COLUMN version TEXT NOT NULL DEFAULT '';
+ALTER TABLE language ADD COLUMN enabled BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE language ADD COLUMN example TEXT NOT NULL DEFAULT '';
+
+-- Keep judging the languages that previously had a built in toolchain.
+UPDATE language SET source = 'main.c', compile = 'gcc -std=c11 -O2 -o main main.c -lm', run = './main', enabled = 1
+    WHERE lower(name) = 'c';
+UPDATE language SET source = 'main.cpp', compile = 'g++ -std=c++14 -O2 -o main main.cpp', run = './main', enabled = 1
+    WHERE lower(name) IN ('c++', 'cpp');
+UPDATE language SET source = 'main.rs', compile = 'rustc -O -o main main.rs', run = './main', enabled = 1
+    WHERE lower(name) = 'rust';
+UPDATE language SET source = 'main.py', run = 'python3 main.py', enabled = 1
+    WHERE lower(name) IN ('python', 'python3');
+UPDATE language SET source = 'Main.java', compile = 'javac Main.java', run = 'java Main', enabled = 1
+    WHERE lower(name) = 'java';
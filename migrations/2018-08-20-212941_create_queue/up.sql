@@ -0,0 +1,10 @@
+CREATE TABLE queue (
+    id INTEGER PRIMARY KEY NOT NULL,
+    sid INTEGER NOT NULL UNIQUE,
+    worker TEXT,
+    claimed_at TIMESTAMP,
+    FOREIGN KEY(sid) REFERENCES solution(id)
+);
+
+-- Anything that hasn't been judged yet needs to be.
+INSERT INTO queue (sid) SELECT id FROM solution WHERE verdict = 'PD';
@@ -0,0 +1,10 @@
+CREATE TABLE problem_old (
+    id INTEGER PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL,
+    description TEXT NOT NULL
+);
+
+INSERT INTO problem_old (id, name, description) SELECT id, name, description FROM problem;
+
+DROP TABLE problem;
+ALTER TABLE problem_old RENAME TO problem;
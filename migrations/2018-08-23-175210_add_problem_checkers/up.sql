@@ -0,0 +1,4 @@
+ALTER TABLE problem ADD COLUMN checker TEXT NOT NULL DEFAULT 'exact';
+ALTER TABLE problem ADD COLUMN tolerance DOUBLE NOT NULL DEFAULT 0.000001;
+ALTER TABLE problem ADD COLUMN checker_language INTEGER REFERENCES language(id);
+ALTER TABLE problem ADD COLUMN checker_code TEXT NOT NULL DEFAULT '';
@@ -0,0 +1,15 @@
+CREATE TABLE problem_old (
+    id INTEGER PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL,
+    description TEXT NOT NULL,
+    checker TEXT NOT NULL DEFAULT 'exact',
+    tolerance DOUBLE NOT NULL DEFAULT 0.000001,
+    checker_language INTEGER REFERENCES language(id),
+    checker_code TEXT NOT NULL DEFAULT ''
+);
+
+INSERT INTO problem_old (id, name, description, checker, tolerance, checker_language, checker_code)
+    SELECT id, name, description, checker, tolerance, checker_language, checker_code FROM problem;
+
+DROP TABLE problem;
+ALTER TABLE problem_old RENAME TO problem;
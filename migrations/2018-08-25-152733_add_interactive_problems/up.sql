@@ -0,0 +1,3 @@
+ALTER TABLE problem ADD COLUMN interactive BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE problem ADD COLUMN interactor_language INTEGER REFERENCES language(id);
+ALTER TABLE problem ADD COLUMN interactor_code TEXT NOT NULL DEFAULT '';
@@ -0,0 +1,2 @@
+DROP TABLE rejudge_change;
+DROP TABLE rejudge;
@@ -0,0 +1,20 @@
+CREATE TABLE rejudge (
+    id INTEGER PRIMARY KEY NOT NULL,
+    description TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE rejudge_change (
+    id INTEGER PRIMARY KEY NOT NULL,
+    rid INTEGER NOT NULL,
+    sid INTEGER NOT NULL,
+    old_verdict TEXT NOT NULL,
+    old_score INTEGER NOT NULL,
+    -- Both are null until the solution has been judged again.
+    new_verdict TEXT,
+    new_score INTEGER,
+    FOREIGN KEY(rid) REFERENCES rejudge(id),
+    FOREIGN KEY(sid) REFERENCES solution(id)
+);
+
+CREATE INDEX rejudge_change_sid ON rejudge_change(sid);
@@ -0,0 +1,12 @@
+CREATE TABLE test_result (
+    id INTEGER PRIMARY KEY NOT NULL,
+    sid INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    verdict TEXT NOT NULL,
+    runtime INTEGER NOT NULL,
+    memory INTEGER NOT NULL,
+    message TEXT NOT NULL,
+    FOREIGN KEY(sid) REFERENCES solution(id)
+);
+
+CREATE INDEX test_result_sid ON test_result(sid);
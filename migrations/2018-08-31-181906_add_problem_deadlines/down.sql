@@ -0,0 +1,20 @@
+CREATE TABLE problem_old (
+    id INTEGER PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL,
+    description TEXT NOT NULL,
+    checker TEXT NOT NULL DEFAULT 'exact',
+    tolerance DOUBLE NOT NULL DEFAULT 0.000001,
+    checker_language INTEGER REFERENCES language(id),
+    checker_code TEXT NOT NULL DEFAULT '',
+    interactive BOOLEAN NOT NULL DEFAULT 0,
+    interactor_language INTEGER REFERENCES language(id),
+    interactor_code TEXT NOT NULL DEFAULT ''
+);
+
+INSERT INTO problem_old (id, name, description, checker, tolerance, checker_language, checker_code,
+                         interactive, interactor_language, interactor_code)
+    SELECT id, name, description, checker, tolerance, checker_language, checker_code,
+           interactive, interactor_language, interactor_code FROM problem;
+
+DROP TABLE problem;
+ALTER TABLE problem_old RENAME TO problem;
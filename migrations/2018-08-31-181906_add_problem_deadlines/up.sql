@@ -0,0 +1 @@
+ALTER TABLE problem ADD COLUMN due_at TIMESTAMP;
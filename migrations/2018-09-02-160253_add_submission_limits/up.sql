@@ -0,0 +1,5 @@
+-- Limits are left null for problems that don't have them.
+ALTER TABLE problem ADD COLUMN max_attempts INTEGER;
+ALTER TABLE problem ADD COLUMN daily_attempts INTEGER;
+-- Seconds a user has to wait after an attempt that wasn't accepted.
+ALTER TABLE problem ADD COLUMN cooldown INTEGER;
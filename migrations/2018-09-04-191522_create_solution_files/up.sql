@@ -0,0 +1,9 @@
+-- Solutions submitted as a single file keep it in solution.code instead.
+CREATE TABLE solution_file (
+    id INTEGER PRIMARY KEY NOT NULL,
+    sid INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    content TEXT NOT NULL,
+    FOREIGN KEY(sid) REFERENCES solution(id),
+    UNIQUE(sid, name)
+);
@@ -0,0 +1,5 @@
+ALTER TABLE test_result ADD COLUMN stderr TEXT NOT NULL DEFAULT '';
+-- These are only kept for sample tests.
+ALTER TABLE test_result ADD COLUMN input TEXT;
+ALTER TABLE test_result ADD COLUMN expected TEXT;
+ALTER TABLE test_result ADD COLUMN output TEXT;
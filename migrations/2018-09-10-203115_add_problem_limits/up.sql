@@ -0,0 +1,13 @@
+ALTER TABLE problem ADD COLUMN time_limit INTEGER NOT NULL DEFAULT 2000;
+ALTER TABLE problem ADD COLUMN memory_limit INTEGER NOT NULL DEFAULT 256;
+ALTER TABLE problem ADD COLUMN output_limit INTEGER NOT NULL DEFAULT 64;
+
+-- Languages without a row get the problem's time limit as it is.
+CREATE TABLE problem_language (
+    pid INTEGER NOT NULL,
+    lid INTEGER NOT NULL,
+    time_multiplier DOUBLE NOT NULL DEFAULT 1.0,
+    PRIMARY KEY(pid, lid),
+    FOREIGN KEY(pid) REFERENCES problem(id),
+    FOREIGN KEY(lid) REFERENCES language(id)
+);
@@ -0,0 +1,7 @@
+-- Judge nodes running on other machines, registered with the server.
+CREATE TABLE judge_node (
+    name TEXT PRIMARY KEY NOT NULL,
+    workers INTEGER NOT NULL,
+    registered_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    seen_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
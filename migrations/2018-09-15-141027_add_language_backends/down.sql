@@ -0,0 +1,16 @@
+CREATE TABLE language_old (
+    id INTEGER PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL,
+    source TEXT NOT NULL DEFAULT '',
+    compile TEXT NOT NULL DEFAULT '',
+    run TEXT NOT NULL DEFAULT '',
+    version TEXT NOT NULL DEFAULT '',
+    enabled BOOLEAN NOT NULL DEFAULT 0,
+    example TEXT NOT NULL DEFAULT ''
+);
+
+INSERT INTO language_old (id, name, source, compile, run, version, enabled, example)
+    SELECT id, name, source, compile, run, version, enabled, example FROM language;
+
+DROP TABLE language;
+ALTER TABLE language_old RENAME TO language;
@@ -0,0 +1,2 @@
+-- Existing languages keep running as native processes.
+ALTER TABLE language ADD COLUMN backend TEXT NOT NULL DEFAULT 'native';
@@ -0,0 +1,2 @@
+-- Problems without a release date are released as soon as they're created.
+ALTER TABLE problem ADD COLUMN release_at TIMESTAMP;
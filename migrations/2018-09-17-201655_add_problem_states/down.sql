@@ -0,0 +1,31 @@
+CREATE TABLE problem_old (
+    id INTEGER PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL,
+    description TEXT NOT NULL,
+    checker TEXT NOT NULL DEFAULT 'exact',
+    tolerance DOUBLE NOT NULL DEFAULT 0.000001,
+    checker_language INTEGER REFERENCES language(id),
+    checker_code TEXT NOT NULL DEFAULT '',
+    interactive BOOLEAN NOT NULL DEFAULT 0,
+    interactor_language INTEGER REFERENCES language(id),
+    interactor_code TEXT NOT NULL DEFAULT '',
+    due_at TIMESTAMP,
+    max_attempts INTEGER,
+    daily_attempts INTEGER,
+    cooldown INTEGER,
+    time_limit INTEGER NOT NULL DEFAULT 2000,
+    memory_limit INTEGER NOT NULL DEFAULT 256,
+    output_limit INTEGER NOT NULL DEFAULT 64,
+    release_at TIMESTAMP
+);
+
+INSERT INTO problem_old (id, name, description, checker, tolerance, checker_language, checker_code,
+                         interactive, interactor_language, interactor_code, due_at,
+                         max_attempts, daily_attempts, cooldown, time_limit, memory_limit, output_limit,
+                         release_at)
+    SELECT id, name, description, checker, tolerance, checker_language, checker_code,
+           interactive, interactor_language, interactor_code, due_at,
+           max_attempts, daily_attempts, cooldown, time_limit, memory_limit, output_limit, release_at FROM problem;
+
+DROP TABLE problem;
+ALTER TABLE problem_old RENAME TO problem;
@@ -0,0 +1,5 @@
+-- Problems that already exist have been visible all along. New problems are
+-- created as drafts by the admin panel.
+ALTER TABLE problem ADD COLUMN state TEXT NOT NULL DEFAULT 'published';
+ALTER TABLE problem ADD COLUMN reference_language INTEGER REFERENCES language(id);
+ALTER TABLE problem ADD COLUMN reference_code TEXT NOT NULL DEFAULT '';
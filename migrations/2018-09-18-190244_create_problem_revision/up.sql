@@ -0,0 +1,14 @@
+CREATE TABLE problem_revision (
+    id INTEGER PRIMARY KEY NOT NULL,
+    pid INTEGER NOT NULL REFERENCES problem(id),
+    -- NULL for the revisions problems had before there was a history.
+    uid INTEGER REFERENCES user(id),
+    name TEXT NOT NULL,
+    description TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    -- The revision this one put back, if it was made by restoring one.
+    restored_from INTEGER REFERENCES problem_revision(id)
+);
+
+-- The history of existing problems starts with how they are now.
+INSERT INTO problem_revision (pid, name, description) SELECT id, name, description FROM problem;
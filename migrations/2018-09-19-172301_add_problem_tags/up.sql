@@ -0,0 +1,15 @@
+-- From 1 for the easiest problems, NULL for problems that haven't been rated.
+ALTER TABLE problem ADD COLUMN difficulty INTEGER;
+
+CREATE TABLE tag (
+    id INTEGER PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL UNIQUE
+);
+
+CREATE TABLE problem_tag (
+    pid INTEGER NOT NULL,
+    tid INTEGER NOT NULL,
+    PRIMARY KEY(pid, tid),
+    FOREIGN KEY(pid) REFERENCES problem(id),
+    FOREIGN KEY(tid) REFERENCES tag(id)
+);
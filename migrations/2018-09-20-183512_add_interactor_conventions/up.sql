@@ -0,0 +1,2 @@
+-- How the interactor reports its verdict, one of 'testlib' or 'kattis'.
+ALTER TABLE problem ADD COLUMN interactor_convention TEXT NOT NULL DEFAULT 'testlib';
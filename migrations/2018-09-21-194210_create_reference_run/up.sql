@@ -0,0 +1,11 @@
+-- The last time each problem's reference solution was judged, with a digest
+-- of the solution, tests and limits it was judged with.
+CREATE TABLE reference_run (
+    pid INTEGER PRIMARY KEY NOT NULL,
+    digest TEXT NOT NULL,
+    done BOOLEAN NOT NULL DEFAULT 0,
+    accepted BOOLEAN NOT NULL DEFAULT 0,
+    message TEXT NOT NULL DEFAULT '',
+    started_at TIMESTAMP NOT NULL,
+    FOREIGN KEY(pid) REFERENCES problem(id)
+);
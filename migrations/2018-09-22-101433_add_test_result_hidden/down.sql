@@ -0,0 +1,22 @@
+CREATE TABLE test_result_old (
+    id INTEGER PRIMARY KEY NOT NULL,
+    sid INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    verdict TEXT NOT NULL,
+    runtime INTEGER NOT NULL,
+    memory INTEGER NOT NULL,
+    message TEXT NOT NULL,
+    stderr TEXT NOT NULL DEFAULT '',
+    input TEXT,
+    expected TEXT,
+    output TEXT,
+    FOREIGN KEY(sid) REFERENCES solution(id)
+);
+
+INSERT INTO test_result_old (id, sid, position, verdict, runtime, memory, message, stderr, input, expected, output)
+    SELECT id, sid, position, verdict, runtime, memory, message, stderr, input, expected, output FROM test_result;
+
+DROP TABLE test_result;
+ALTER TABLE test_result_old RENAME TO test_result;
+
+CREATE INDEX test_result_sid ON test_result(sid);
@@ -0,0 +1,3 @@
+-- Only sample tests show what the solution wrote to stderr to its author.
+ALTER TABLE test_result ADD COLUMN hidden BOOLEAN NOT NULL DEFAULT 0;
+UPDATE test_result SET hidden = 1 WHERE expected IS NULL;
@@ -0,0 +1,43 @@
+//! Judges solutions for a server from another machine. The node is
+//! configured with the environment or a .env file, see the README.
+
+extern crate dotenv;
+extern crate env_logger;
+extern crate potw;
+
+use std::process;
+
+use potw::judge::{ Judge, node::{ self, Config } };
+
+fn main() {
+    dotenv::dotenv().ok();
+    std::env::set_var("RUST_LOG", "potw=info");
+    env_logger::init();
+
+    let token = match std::env::var("POTW_NODE_TOKEN") {
+        Ok(ref token) if !token.is_empty() => token.clone(),
+        _ => {
+            eprintln!("POTW_NODE_TOKEN has to be set to the token the server was given.");
+            process::exit(1);
+        }
+    };
+
+    let judge_dir = std::env::var("POTW_JUDGE_DIR")
+        .map(Into::into)
+        .unwrap_or_else(|_| std::env::temp_dir().join("potw-node"));
+
+    let config = Config {
+        server: std::env::var("POTW_SERVER").unwrap_or_else(|_| "http://127.0.0.1:8080".into()),
+        token: token,
+        name: std::env::var("POTW_NODE_NAME").unwrap_or_else(|_| "node".into()),
+        workers: std::env::var("POTW_JUDGE_WORKERS")
+            .ok()
+            .and_then(|workers| workers.parse().ok())
+            .unwrap_or(2),
+    };
+
+    if let Err(err) = node::run(config, Judge::new(judge_dir)) {
+        eprintln!("The judge node stopped: {}", err);
+        process::exit(1);
+    }
+}
@@ -1,3 +1,43 @@
+use diesel::{ Connection, ConnectionResult, ConnectionError, SqliteConnection, result::Error };
 
 pub mod models;
 pub mod schema;
+
+const DATABASE: &str = "database.db";
+
+no_arg_sql_function!(last_insert_rowid, ::diesel::sql_types::Integer,
+                     "Represents the SQLite last_insert_rowid() function.");
+
+/// Opens a connection to the database. The judge workers write from their
+/// own connections so connections wait on each other's locks instead of
+/// failing right away.
+pub fn establish() -> ConnectionResult<SqliteConnection> {
+    let conn = SqliteConnection::establish(DATABASE)?;
+
+    conn.execute("PRAGMA journal_mode = WAL;")
+        .and_then(|_| conn.execute("PRAGMA busy_timeout = 10000;"))
+        .map_err(ConnectionError::CouldntSetupConfiguration)?;
+
+    Ok(conn)
+}
+
+/// Runs `f` in a transaction that takes the write lock before anything is
+/// read. Diesel's transactions only take it on their first write, and one
+/// that read something another has written since can't write at all, which
+/// `busy_timeout` doesn't wait out. Nothing in `f` can start a transaction.
+pub fn immediate_transaction<T, E, F>(conn: &SqliteConnection, f: F) -> Result<T, E>
+    where F: FnOnce() -> Result<T, E>, E: From<Error>
+{
+    conn.execute("BEGIN IMMEDIATE")?;
+
+    let result = f().and_then(|value| {
+        conn.execute("COMMIT")?;
+        Ok(value)
+    });
+
+    if result.is_err() {
+        let _ = conn.execute("ROLLBACK");
+    }
+
+    result
+}
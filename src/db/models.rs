@@ -1,6 +1,18 @@
+use chrono::NaiveDateTime;
 use liquid::{ Object, Value };
 use comrak::{ self, ComrakOptions };
 
+use ::judge::Verdict;
+use ::visibility::is_released;
+
+/// The format of `datetime-local` form inputs.
+pub const DATETIME_INPUT: &str = "%Y-%m-%dT%H:%M";
+
+/// Formats a timestamp for displaying in templates.
+pub fn format_time(time: &NaiveDateTime) -> String {
+    time.format("%Y-%m-%d %H:%M").to_string()
+}
+
 #[derive(Clone, Queryable)]
 pub struct User {
     pub id: i32,
@@ -23,10 +35,105 @@ pub struct Problem {
     pub id: i32,
     pub name: String,
     pub description: String,
+    /// How output is checked, one of `judge::Checker::MODES`.
+    pub checker: String,
+    pub tolerance: f64,
+    pub checker_language: Option<i32>,
+    pub checker_code: String,
+    /// Solutions to interactive problems talk to the interactor instead of
+    /// reading a test's input.
+    pub interactive: bool,
+    pub interactor_language: Option<i32>,
+    pub interactor_code: String,
+    /// Other users' accepted solutions may be shown once this has passed.
+    pub due_at: Option<NaiveDateTime>,
+    /// How many solutions a user can submit in total.
+    pub max_attempts: Option<i32>,
+    /// How many solutions a user can submit each day.
+    pub daily_attempts: Option<i32>,
+    /// Seconds a user has to wait after a solution that wasn't accepted.
+    pub cooldown: Option<i32>,
+    /// CPU time in milliseconds.
+    pub time_limit: i32,
+    /// Memory in megabytes.
+    pub memory_limit: i32,
+    /// The most a solution can output in megabytes.
+    pub output_limit: i32,
+    /// Only admins can see the problem until this has passed.
+    pub release_at: Option<NaiveDateTime>,
+    /// One of the codes of `ProblemState`.
+    pub state: String,
+    /// A solution that has to be accepted before the problem is published.
+    pub reference_language: Option<i32>,
+    pub reference_code: String,
+    /// One of `Problem::DIFFICULTIES`, `None` if it hasn't been rated.
+    pub difficulty: Option<i32>,
+    /// How the interactor reports its verdict, one of
+    /// `judge::Convention::MODES`.
+    pub interactor_convention: String,
+}
+
+/// Drafts are only seen by admins. Archived problems can still be read but
+/// don't accept solutions anymore.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProblemState {
+    Draft,
+    Published,
+    Archived,
+}
+
+impl ProblemState {
+    /// How the state is stored.
+    pub fn code(&self) -> &'static str {
+        match *self {
+            ProblemState::Draft => "draft",
+            ProblemState::Published => "published",
+            ProblemState::Archived => "archived",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        match code {
+            "draft" => Some(ProblemState::Draft),
+            "published" => Some(ProblemState::Published),
+            "archived" => Some(ProblemState::Archived),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            ProblemState::Draft => "Draft",
+            ProblemState::Published => "Published",
+            ProblemState::Archived => "Archived",
+        }
+    }
 }
 
 impl Problem {
+    /// Every difficulty a problem can be rated with and its name, from the
+    /// easiest.
+    pub const DIFFICULTIES: &'static [(i32, &'static str)] = &[
+        (1, "Easy"),
+        (2, "Medium"),
+        (3, "Hard"),
+    ];
+
+    pub fn difficulty_name(&self) -> Option<&'static str> {
+        Problem::DIFFICULTIES.iter()
+            .find(|&&(level, _)| Some(level) == self.difficulty)
+            .map(|&(_, name)| name)
+    }
+
+    pub fn state(&self) -> ProblemState {
+        ProblemState::from_code(&self.state).unwrap_or(ProblemState::Draft)
+    }
+
     pub fn to_liquid(self, render: bool) -> Object {
+        let released = is_released(&self);
+        let state = self.state();
+        let difficulty = self.difficulty_name();
+
         let mut obj = Object::new();
         obj.insert("id".into(), Value::scalar(self.id));
         obj.insert("name".into(), Value::scalar(self.name));
@@ -35,6 +142,80 @@ impl Problem {
             obj.insert("description".into(), Value::scalar(rendered));
         } else {
             obj.insert("description".into(), Value::scalar(self.description));
+            obj.insert("checker".into(), Value::scalar(self.checker));
+            obj.insert("tolerance".into(), Value::scalar(self.tolerance));
+            if let Some(checker_language) = self.checker_language {
+                obj.insert("checker_language".into(), Value::scalar(checker_language));
+            }
+            obj.insert("checker_code".into(), Value::scalar(self.checker_code));
+            if let Some(interactor_language) = self.interactor_language {
+                obj.insert("interactor_language".into(), Value::scalar(interactor_language));
+            }
+            obj.insert("interactor_code".into(), Value::scalar(self.interactor_code));
+            obj.insert("interactor_convention".into(), Value::scalar(self.interactor_convention));
+            if let Some(reference_language) = self.reference_language {
+                obj.insert("reference_language".into(), Value::scalar(reference_language));
+            }
+            obj.insert("reference_code".into(), Value::scalar(self.reference_code));
+        }
+        obj.insert("interactive".into(), Value::scalar(self.interactive));
+        obj.insert("time_limit".into(), Value::scalar(self.time_limit));
+        obj.insert("memory_limit".into(), Value::scalar(self.memory_limit));
+        obj.insert("output_limit".into(), Value::scalar(self.output_limit));
+        if let Some(due_at) = self.due_at {
+            obj.insert("due_at".into(), Value::scalar(format_time(&due_at)));
+        }
+        if let Some(release_at) = self.release_at {
+            obj.insert("release_at".into(), Value::scalar(format_time(&release_at)));
+        }
+        obj.insert("released".into(), Value::scalar(released));
+        obj.insert("state".into(), Value::scalar(state.code()));
+        obj.insert("state_name".into(), Value::scalar(state.name()));
+        if let (Some(level), Some(name)) = (self.difficulty, difficulty) {
+            obj.insert("difficulty".into(), Value::scalar(level));
+            obj.insert("difficulty_name".into(), Value::scalar(name));
+        }
+        if !render {
+            let input = |time: Option<NaiveDateTime>| time
+                .map(|time| time.format(DATETIME_INPUT).to_string())
+                .unwrap_or_default();
+            obj.insert("due_at_input".into(), Value::scalar(input(self.due_at)));
+            obj.insert("release_at_input".into(), Value::scalar(input(self.release_at)));
+
+            let limit = |limit: Option<i32>| limit.map(|limit| limit.to_string()).unwrap_or_default();
+            obj.insert("max_attempts".into(), Value::scalar(limit(self.max_attempts)));
+            obj.insert("daily_attempts".into(), Value::scalar(limit(self.daily_attempts)));
+            obj.insert("cooldown".into(), Value::scalar(limit(self.cooldown)));
+        }
+        obj
+    }
+}
+
+/// The name and description of a problem after one of its edits.
+#[derive(Clone, Queryable)]
+pub struct ProblemRevision {
+    pub id: i32,
+    pub pid: i32,
+    /// The admin who made the edit, `None` for revisions from before
+    /// problems had a history.
+    pub uid: Option<i32>,
+    pub name: String,
+    pub description: String,
+    pub created_at: NaiveDateTime,
+    /// The revision that was restored to make this one.
+    pub restored_from: Option<i32>,
+}
+
+impl ProblemRevision {
+    pub fn to_liquid(self) -> Object {
+        let mut obj = Object::new();
+        obj.insert("id".into(), Value::scalar(self.id));
+        obj.insert("pid".into(), Value::scalar(self.pid));
+        obj.insert("name".into(), Value::scalar(self.name));
+        obj.insert("description".into(), Value::scalar(self.description));
+        obj.insert("created_at".into(), Value::scalar(format_time(&self.created_at)));
+        if let Some(restored_from) = self.restored_from {
+            obj.insert("restored_from".into(), Value::scalar(restored_from));
         }
         obj
     }
@@ -47,16 +228,163 @@ pub struct Solution {
     pub uid: i32,
     pub language: i32,
     pub code: String,
+    pub verdict: String,
+    pub score: i32,
+    pub runtime: Option<i32>,
+    pub memory: Option<i32>,
+    pub message: String,
+    pub submitted_at: NaiveDateTime,
+    pub judged_at: Option<NaiveDateTime>,
 }
 
 impl Solution {
+    pub fn verdict(&self) -> Verdict {
+        Verdict::from_code(&self.verdict).unwrap_or(Verdict::Pending)
+    }
+
     pub fn to_liquid(self) -> Object {
+        let verdict = self.verdict();
+
         let mut obj = Object::new();
         obj.insert("id".into(), Value::scalar(self.id));
         obj.insert("pid".into(), Value::scalar(self.pid));
         obj.insert("uid".into(), Value::scalar(self.uid));
         obj.insert("language".into(), Value::scalar(self.language));
         obj.insert("code".into(), Value::scalar(self.code));
+        obj.insert("verdict".into(), Value::scalar(verdict.code()));
+        obj.insert("verdict_name".into(), Value::scalar(verdict.name()));
+        obj.insert("score".into(), Value::scalar(self.score));
+        if let Some(runtime) = self.runtime {
+            obj.insert("runtime".into(), Value::scalar(runtime));
+        }
+        if let Some(memory) = self.memory {
+            obj.insert("memory".into(), Value::scalar(memory));
+        }
+        obj.insert("message".into(), Value::scalar(self.message));
+        obj.insert("submitted_at".into(), Value::scalar(format_time(&self.submitted_at)));
+        if let Some(judged_at) = self.judged_at {
+            obj.insert("judged_at".into(), Value::scalar(format_time(&judged_at)));
+        }
+        obj
+    }
+}
+
+/// A file of a solution that was submitted as more than one file.
+#[derive(Clone, Queryable)]
+pub struct SolutionFile {
+    pub id: i32,
+    pub sid: i32,
+    /// A path relative to the directory the solution is built in.
+    pub name: String,
+    pub content: String,
+}
+
+impl SolutionFile {
+    pub fn to_liquid(self) -> Object {
+        let mut obj = Object::new();
+        obj.insert("id".into(), Value::scalar(self.id));
+        obj.insert("sid".into(), Value::scalar(self.sid));
+        obj.insert("name".into(), Value::scalar(self.name));
+        obj.insert("content".into(), Value::scalar(self.content));
+        obj
+    }
+}
+
+/// A judge node on another machine.
+#[derive(Clone, Queryable)]
+pub struct JudgeNode {
+    pub name: String,
+    pub workers: i32,
+    pub registered_at: NaiveDateTime,
+    /// The last time the node was heard from.
+    pub seen_at: NaiveDateTime,
+}
+
+impl JudgeNode {
+    pub fn to_liquid(self) -> Object {
+        let mut obj = Object::new();
+        obj.insert("name".into(), Value::scalar(self.name));
+        obj.insert("workers".into(), Value::scalar(self.workers));
+        obj.insert("registered_at".into(), Value::scalar(format_time(&self.registered_at)));
+        obj.insert("seen_at".into(), Value::scalar(format_time(&self.seen_at)));
+        obj
+    }
+}
+
+/// A solution waiting to be judged.
+#[derive(Clone, Queryable)]
+pub struct QueueEntry {
+    pub id: i32,
+    pub sid: i32,
+    /// The worker judging the solution, if one has claimed it.
+    pub worker: Option<String>,
+    pub claimed_at: Option<NaiveDateTime>,
+}
+
+/// The last time a problem's reference solution was judged.
+#[derive(Clone, Queryable)]
+pub struct ReferenceRun {
+    pub pid: i32,
+    /// Identifies the solution, tests and limits it was judged with.
+    pub digest: String,
+    /// Whether judging has finished.
+    pub done: bool,
+    pub accepted: bool,
+    /// Why the solution wasn't accepted.
+    pub message: String,
+    pub started_at: NaiveDateTime,
+}
+
+/// A group of solutions that were queued to be judged again.
+#[derive(Clone, Queryable)]
+pub struct Rejudge {
+    pub id: i32,
+    pub description: String,
+    pub created_at: NaiveDateTime,
+}
+
+impl Rejudge {
+    pub fn to_liquid(self) -> Object {
+        let mut obj = Object::new();
+        obj.insert("id".into(), Value::scalar(self.id));
+        obj.insert("description".into(), Value::scalar(self.description));
+        obj.insert("created_at".into(), Value::scalar(format_time(&self.created_at)));
+        obj
+    }
+}
+
+/// The verdict of a solution before and after it was judged again.
+#[derive(Clone, Queryable)]
+pub struct RejudgeChange {
+    pub id: i32,
+    pub rid: i32,
+    pub sid: i32,
+    pub old_verdict: String,
+    pub old_score: i32,
+    /// `None` until the solution has been judged again.
+    pub new_verdict: Option<String>,
+    pub new_score: Option<i32>,
+}
+
+impl RejudgeChange {
+    pub fn to_liquid(self) -> Object {
+        let old = Verdict::from_code(&self.old_verdict).unwrap_or(Verdict::Pending);
+
+        let mut obj = Object::new();
+        obj.insert("id".into(), Value::scalar(self.id));
+        obj.insert("sid".into(), Value::scalar(self.sid));
+        obj.insert("old_verdict".into(), Value::scalar(old.code()));
+        obj.insert("old_verdict_name".into(), Value::scalar(old.name()));
+        obj.insert("old_score".into(), Value::scalar(self.old_score));
+        if let Some(new_verdict) = self.new_verdict {
+            let new = Verdict::from_code(&new_verdict).unwrap_or(Verdict::Pending);
+            obj.insert("new_verdict".into(), Value::scalar(new.code()));
+            obj.insert("new_verdict_name".into(), Value::scalar(new.name()));
+            obj.insert("changed".into(), Value::scalar(new != old || self.new_score != Some(self.old_score)));
+        }
+        if let Some(new_score) = self.new_score {
+            obj.insert("new_score".into(), Value::scalar(new_score));
+        }
         obj
     }
 }
@@ -65,6 +393,17 @@ impl Solution {
 pub struct Language {
     pub id: i32,
     pub name: String,
+    /// The file name solutions are written to.
+    pub source: String,
+    /// Empty for languages that don't need to be compiled.
+    pub compile: String,
+    pub run: String,
+    pub version: String,
+    pub enabled: bool,
+    /// A hello world program used to check the toolchain works.
+    pub example: String,
+    /// How solutions are run, one of `judge::Backend::NAMES`.
+    pub backend: String,
 }
 
 impl Language {
@@ -72,6 +411,84 @@ impl Language {
         let mut obj = Object::new();
         obj.insert("id".into(), Value::scalar(self.id));
         obj.insert("name".into(), Value::scalar(self.name));
+        obj.insert("source".into(), Value::scalar(self.source));
+        obj.insert("compile".into(), Value::scalar(self.compile));
+        obj.insert("run".into(), Value::scalar(self.run));
+        obj.insert("version".into(), Value::scalar(self.version));
+        obj.insert("enabled".into(), Value::scalar(self.enabled));
+        obj.insert("example".into(), Value::scalar(self.example));
+        obj.insert("backend".into(), Value::scalar(self.backend));
+        obj
+    }
+}
+
+#[derive(Clone, Queryable)]
+pub struct TestCase {
+    pub id: i32,
+    pub pid: i32,
+    pub position: i32,
+    pub input: String,
+    pub output: String,
+    pub sample: bool,
+}
+
+impl TestCase {
+    pub fn to_liquid(self) -> Object {
+        let mut obj = Object::new();
+        obj.insert("id".into(), Value::scalar(self.id));
+        obj.insert("pid".into(), Value::scalar(self.pid));
+        obj.insert("position".into(), Value::scalar(self.position));
+        obj.insert("input".into(), Value::scalar(self.input));
+        obj.insert("output".into(), Value::scalar(self.output));
+        obj.insert("sample".into(), Value::scalar(self.sample));
+        obj
+    }
+}
+
+/// How a solution did on one of its problem's tests.
+#[derive(Clone, Queryable)]
+pub struct TestResult {
+    pub id: i32,
+    pub sid: i32,
+    pub position: i32,
+    pub verdict: String,
+    pub runtime: i32,
+    pub memory: i32,
+    pub message: String,
+    /// The start of what the solution wrote to stderr.
+    pub stderr: String,
+    /// The test and the solution's output, only kept for sample tests.
+    pub input: Option<String>,
+    pub expected: Option<String>,
+    pub output: Option<String>,
+    /// Only admins see what the solution wrote to stderr on hidden tests.
+    pub hidden: bool,
+}
+
+impl TestResult {
+    pub fn to_liquid(self) -> Object {
+        let verdict = Verdict::from_code(&self.verdict).unwrap_or(Verdict::Pending);
+
+        let mut obj = Object::new();
+        obj.insert("id".into(), Value::scalar(self.id));
+        obj.insert("sid".into(), Value::scalar(self.sid));
+        obj.insert("position".into(), Value::scalar(self.position));
+        obj.insert("verdict".into(), Value::scalar(verdict.code()));
+        obj.insert("verdict_name".into(), Value::scalar(verdict.name()));
+        obj.insert("runtime".into(), Value::scalar(self.runtime));
+        obj.insert("memory".into(), Value::scalar(self.memory));
+        obj.insert("message".into(), Value::scalar(self.message));
+        obj.insert("stderr".into(), Value::scalar(self.stderr));
+        obj.insert("hidden".into(), Value::scalar(self.hidden));
+        if let Some(input) = self.input {
+            obj.insert("input".into(), Value::scalar(input));
+        }
+        if let Some(expected) = self.expected {
+            obj.insert("expected".into(), Value::scalar(expected));
+        }
+        if let Some(output) = self.output {
+            obj.insert("output".into(), Value::scalar(output));
+        }
         obj
     }
 }
@@ -1,7 +1,23 @@
+table! {
+    judge_node (name) {
+        name -> Text,
+        workers -> Integer,
+        registered_at -> Timestamp,
+        seen_at -> Timestamp,
+    }
+}
+
 table! {
     language (id) {
         id -> Integer,
         name -> Text,
+        source -> Text,
+        compile -> Text,
+        run -> Text,
+        version -> Text,
+        enabled -> Bool,
+        example -> Text,
+        backend -> Text,
     }
 }
 
@@ -17,6 +33,93 @@ table! {
         id -> Integer,
         name -> Text,
         description -> Text,
+        checker -> Text,
+        tolerance -> Double,
+        checker_language -> Nullable<Integer>,
+        checker_code -> Text,
+        interactive -> Bool,
+        interactor_language -> Nullable<Integer>,
+        interactor_code -> Text,
+        due_at -> Nullable<Timestamp>,
+        max_attempts -> Nullable<Integer>,
+        daily_attempts -> Nullable<Integer>,
+        cooldown -> Nullable<Integer>,
+        time_limit -> Integer,
+        memory_limit -> Integer,
+        output_limit -> Integer,
+        release_at -> Nullable<Timestamp>,
+        state -> Text,
+        reference_language -> Nullable<Integer>,
+        reference_code -> Text,
+        difficulty -> Nullable<Integer>,
+        interactor_convention -> Text,
+    }
+}
+
+table! {
+    problem_language (pid, lid) {
+        pid -> Integer,
+        lid -> Integer,
+        time_multiplier -> Double,
+    }
+}
+
+table! {
+    problem_revision (id) {
+        id -> Integer,
+        pid -> Integer,
+        uid -> Nullable<Integer>,
+        name -> Text,
+        description -> Text,
+        created_at -> Timestamp,
+        restored_from -> Nullable<Integer>,
+    }
+}
+
+table! {
+    problem_tag (pid, tid) {
+        pid -> Integer,
+        tid -> Integer,
+    }
+}
+
+table! {
+    queue (id) {
+        id -> Integer,
+        sid -> Integer,
+        worker -> Nullable<Text>,
+        claimed_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    reference_run (pid) {
+        pid -> Integer,
+        digest -> Text,
+        done -> Bool,
+        accepted -> Bool,
+        message -> Text,
+        started_at -> Timestamp,
+    }
+}
+
+table! {
+    rejudge (id) {
+        id -> Integer,
+        description -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    rejudge_change (id) {
+        id -> Integer,
+        rid -> Integer,
+        sid -> Integer,
+        old_verdict -> Text,
+        old_score -> Integer,
+        new_verdict -> Nullable<Text>,
+        new_score -> Nullable<Integer>,
     }
 }
 
@@ -27,6 +130,57 @@ table! {
         uid -> Integer,
         language -> Integer,
         code -> Text,
+        verdict -> Text,
+        score -> Integer,
+        runtime -> Nullable<Integer>,
+        memory -> Nullable<Integer>,
+        message -> Text,
+        submitted_at -> Timestamp,
+        judged_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    solution_file (id) {
+        id -> Integer,
+        sid -> Integer,
+        name -> Text,
+        content -> Text,
+    }
+}
+
+table! {
+    tag (id) {
+        id -> Integer,
+        name -> Text,
+    }
+}
+
+table! {
+    test_case (id) {
+        id -> Integer,
+        pid -> Integer,
+        position -> Integer,
+        input -> Text,
+        output -> Text,
+        sample -> Bool,
+    }
+}
+
+table! {
+    test_result (id) {
+        id -> Integer,
+        sid -> Integer,
+        position -> Integer,
+        verdict -> Text,
+        runtime -> Integer,
+        memory -> Integer,
+        message -> Text,
+        stderr -> Text,
+        input -> Nullable<Text>,
+        expected -> Nullable<Text>,
+        output -> Nullable<Text>,
+        hidden -> Bool,
     }
 }
 
@@ -38,14 +192,39 @@ table! {
     }
 }
 
+joinable!(problem_language -> language (lid));
+joinable!(problem_language -> problem (pid));
+joinable!(problem_revision -> problem (pid));
+joinable!(problem_revision -> user (uid));
+joinable!(problem_tag -> problem (pid));
+joinable!(problem_tag -> tag (tid));
+joinable!(queue -> solution (sid));
+joinable!(reference_run -> problem (pid));
+joinable!(rejudge_change -> rejudge (rid));
+joinable!(rejudge_change -> solution (sid));
 joinable!(solution -> language (language));
 joinable!(solution -> problem (pid));
 joinable!(solution -> user (uid));
+joinable!(solution_file -> solution (sid));
+joinable!(test_case -> problem (pid));
+joinable!(test_result -> solution (sid));
 
 allow_tables_to_appear_in_same_query!(
+    judge_node,
     language,
     oauth,
     problem,
+    problem_language,
+    problem_revision,
+    problem_tag,
+    queue,
+    reference_run,
+    rejudge,
+    rejudge_change,
     solution,
+    solution_file,
+    tag,
+    test_case,
+    test_result,
     user,
 );
@@ -0,0 +1,103 @@
+//! Line based diffs for showing how two texts differ.
+
+use liquid::{ Object, Value };
+
+/// Texts with more lines than this multiplied together aren't compared line
+/// by line, every line is shown as changed instead.
+const MAX_CELLS: usize = 1_000_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Line<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+impl<'a> Line<'a> {
+    pub fn to_liquid(&self) -> Object {
+        let (kind, text) = match *self {
+            Line::Same(text) => ("same", text),
+            Line::Removed(text) => ("removed", text),
+            Line::Added(text) => ("added", text),
+        };
+
+        let mut obj = Object::new();
+        obj.insert("kind".into(), Value::scalar(kind));
+        obj.insert("text".into(), Value::scalar(text.to_string()));
+        obj
+    }
+}
+
+/// Finds the lines removed from `old` and added in `new` using the longest
+/// common subsequence of their lines.
+pub fn lines<'a>(old: &'a str, new: &'a str) -> Vec<Line<'a>> {
+    let old = old.lines().collect::<Vec<_>>();
+    let new = new.lines().collect::<Vec<_>>();
+
+    if old.len().saturating_mul(new.len()) > MAX_CELLS {
+        return old.iter().map(|line| Line::Removed(line))
+            .chain(new.iter().map(|line| Line::Added(line)))
+            .collect();
+    }
+
+    // lcs[i][j] is the length of the longest common subsequence of
+    // old[i..] and new[j..].
+    let mut lcs = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            diff.push(Line::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            diff.push(Line::Removed(old[i]));
+            i += 1;
+        } else {
+            diff.push(Line::Added(new[j]));
+            j += 1;
+        }
+    }
+
+    diff.extend(old[i..].iter().map(|line| Line::Removed(line)));
+    diff.extend(new[j..].iter().map(|line| Line::Added(line)));
+    diff
+}
+
+/// Converts a diff to a list of lines for templates.
+pub fn to_liquid(diff: &[Line]) -> Value {
+    Value::array(diff.iter().map(|line| Value::Object(line.to_liquid())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_text_is_all_added_or_removed() {
+        assert_eq!(lines("", "a\nb\n"), vec![Line::Added("a"), Line::Added("b")]);
+        assert_eq!(lines("a\nb", ""), vec![Line::Removed("a"), Line::Removed("b")]);
+        assert_eq!(lines("", ""), vec![]);
+    }
+
+    #[test]
+    fn unchanged_lines_are_kept() {
+        assert_eq!(lines("a\nb\nc", "a\nx\nc\nd"), vec![
+            Line::Same("a"),
+            Line::Removed("b"),
+            Line::Added("x"),
+            Line::Same("c"),
+            Line::Added("d"),
+        ]);
+    }
+}
@@ -0,0 +1,228 @@
+//! Unpacks solutions and problem packages uploaded as zip or tar archives.
+//! Archives are extracted by the system's `unzip` and `tar` inside a
+//! sandbox, and only the regular files they contain are read back out.
+//! Problem packages are packed with `zip` the same way.
+//!
+//! Archives are listed before they're extracted so ones with too many files
+//! are turned away early. They're then extracted into the sandbox's `/tmp`,
+//! which can only hold so much, so archives that expand to more than that
+//! fail instead of filling the disk.
+
+use std::fs;
+use std::io::Read;
+use std::path::{ Component, Path };
+use std::thread;
+
+use failure::{ self, Error };
+use futures::{ future, Future, sync::oneshot };
+
+use super::{ Judge, Limits, Sandbox, SourceFile, Status };
+
+/// The most files a solution can have.
+pub const MAX_FILES: usize = 64;
+
+/// The most bytes all of a solution's files can add up to.
+pub const MAX_SIZE: u64 = 1024 * 1024;
+
+/// The most files a problem package can have, test data included.
+pub const MAX_PACKAGE_FILES: usize = 4096;
+
+/// The most bytes all of a problem package's files can add up to.
+pub const MAX_PACKAGE_SIZE: u64 = 64 * 1024 * 1024;
+
+const ARCHIVE: &str = "archive";
+const FILES: &str = "files";
+const LOG: &str = "extract.log";
+
+/// Where archives are extracted before they're copied to `FILES`.
+const STAGING: &str = "/tmp/files";
+
+/// What extracting exits with when an archive has too many files.
+const TOO_MANY_FILES: i32 = 3;
+
+/// Checks if an uploaded file looks like an archive by its name.
+pub fn is_archive(name: &str) -> bool {
+    let name = name.to_lowercase();
+    [".zip", ".tar", ".tar.gz", ".tgz", ".tar.bz2", ".tar.xz"].iter()
+        .any(|ext| name.ends_with(ext))
+}
+
+/// Checks that a file name stays inside the directory it's written to and
+/// only has letters, numbers, dots, dashes and underscores, with `/` between
+/// folders. Names are shown on pages so nothing else is allowed in them.
+pub fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-' || c == '/')
+        && Path::new(name).components().all(|part| match part {
+            Component::Normal(_) => true,
+            _ => false,
+        })
+}
+
+impl Judge {
+    /// Extracts the source files from an archive.
+    pub fn extract(&self, name: String, data: Vec<u8>) -> Box<Future<Item = Vec<SourceFile>, Error = Error>> {
+        self.unpack_in_background(name, data, MAX_FILES, MAX_SIZE)
+    }
+
+    /// Extracts the files of a problem package.
+    pub fn extract_package(&self, name: String, data: Vec<u8>) -> Box<Future<Item = Vec<SourceFile>, Error = Error>> {
+        self.unpack_in_background(name, data, MAX_PACKAGE_FILES, MAX_PACKAGE_SIZE)
+    }
+
+    /// Packs files into a zip archive, inside of a folder with the given name.
+    pub fn pack(&self, folder: &str, files: &[SourceFile]) -> Result<Vec<u8>, Error> {
+        if !valid_name(folder) {
+            return Err(failure::err_msg(format!("\"{}\" isn't a valid folder name.", folder)));
+        }
+
+        let sandbox = Sandbox::new(&self.root)?;
+        for file in files {
+            if !valid_name(&file.name) {
+                return Err(failure::err_msg(format!("\"{}\" isn't a valid file name.", file.name)));
+            }
+            sandbox.write(&format!("{}/{}/{}", FILES, folder, file.name), &file.content)?;
+        }
+
+        let limits = Limits {
+            time: 10_000,
+            wall_time: 20_000,
+            memory: None,
+            output: MAX_PACKAGE_SIZE / 1024,
+        };
+
+        let cmd = format!("cd {} && zip -qr ../{} {}", FILES, ARCHIVE, folder);
+        let exec = sandbox.run(&cmd, &limits, None, LOG, LOG)?;
+        if !exec.success() {
+            return Err(failure::err_msg("The archive couldn't be created."));
+        }
+
+        let mut archive = Vec::new();
+        sandbox.open(ARCHIVE)?.read_to_end(&mut archive)?;
+        Ok(archive)
+    }
+
+    /// Unpacks an archive on a thread of its own, so whoever is waiting on
+    /// it isn't held up.
+    fn unpack_in_background(&self, name: String, data: Vec<u8>, max_files: usize, max_size: u64) -> Box<Future<Item = Vec<SourceFile>, Error = Error>> {
+        let (reply, result) = oneshot::channel();
+        let judge = self.clone();
+
+        let spawned = thread::Builder::new()
+            .name("extract".into())
+            .spawn(move || {
+                let _ = reply.send(judge.unpack(&name, &data, max_files, max_size));
+            });
+
+        if let Err(err) = spawned {
+            return Box::new(future::err(Error::from(err)));
+        }
+
+        let result = result
+            .map_err(|_| failure::err_msg("The archive stopped being extracted."))
+            .and_then(|result| result);
+
+        Box::new(result)
+    }
+
+    /// Extracts an archive, failing if it has more than `max_files` files or
+    /// they add up to more than `max_size` bytes.
+    fn unpack(&self, name: &str, data: &[u8], max_files: usize, max_size: u64) -> Result<Vec<SourceFile>, Error> {
+        let sandbox = Sandbox::new(&self.root)?;
+        sandbox.write(ARCHIVE, data)?;
+        sandbox.create_dir(FILES)?;
+
+        let (list, extract) = if name.to_lowercase().ends_with(".zip") {
+            (format!("unzip -Z1 {}", ARCHIVE), format!("unzip -qq {} -d {}", ARCHIVE, STAGING))
+        } else {
+            (format!("tar -tf {}", ARCHIVE), format!("tar -xf {} -C {}", ARCHIVE, STAGING))
+        };
+
+        // Folders are listed on their own with a trailing slash.
+        let cmd = format!(
+            "n=$({} | grep -vc '/$'); [ \"$n\" -le {} ] || exit {}; mkdir {} && {} && cp -R {}/. {}/",
+            list, max_files, TOO_MANY_FILES, STAGING, extract, STAGING, FILES,
+        );
+
+        let limits = Limits {
+            time: 10_000,
+            wall_time: 20_000,
+            memory: None,
+            output: max_size / 1024,
+        };
+
+        let exec = sandbox.run(&cmd, &limits, None, LOG, LOG)?;
+        if exec.status == Status::Exited(TOO_MANY_FILES) {
+            return Err(failure::err_msg(format!("Archives can't have more than {} files.", max_files)));
+        }
+        if !exec.success() {
+            return Err(failure::err_msg("The archive couldn't be extracted, it may be damaged or too large."));
+        }
+
+        let mut files = Vec::new();
+        let mut size = 0;
+        collect(&sandbox.path().join(FILES), "", &mut files, &mut size, (max_files, max_size))?;
+
+        // Archives of a single folder are extracted as if they were the
+        // folder's contents.
+        let prefix = files.first()
+            .and_then(|file| file.name.find('/').map(|i| file.name[..i + 1].to_string()));
+
+        if let Some(prefix) = prefix {
+            if files.iter().all(|file| file.name.starts_with(&prefix)) {
+                for file in &mut files {
+                    file.name = file.name[prefix.len()..].into();
+                }
+            }
+        }
+
+        if files.is_empty() {
+            return Err(failure::err_msg("The archive doesn't have any files."));
+        }
+
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(files)
+    }
+}
+
+/// Reads every regular file in a directory. Hidden files and anything that
+/// isn't a file or directory, such as links, are skipped.
+fn collect(dir: &Path, prefix: &str, files: &mut Vec<SourceFile>, size: &mut u64, max: (usize, u64)) -> Result<(), Error> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') || name == "__MACOSX" {
+            continue;
+        }
+
+        let path = format!("{}{}", prefix, name);
+        let meta = fs::symlink_metadata(entry.path())?;
+
+        if meta.is_dir() {
+            collect(&entry.path(), &format!("{}/", path), files, size, max)?;
+        } else if meta.is_file() {
+            if !valid_name(&path) {
+                return Err(failure::err_msg(format!("\"{}\" isn't a valid file name.", path)));
+            }
+
+            *size += meta.len();
+            let (max_files, max_size) = max;
+            if files.len() >= max_files {
+                return Err(failure::err_msg(format!("Archives can't have more than {} files.", max_files)));
+            }
+            if *size > max_size {
+                return Err(failure::err_msg(format!("Archives can't be larger than {} KB.", max_size / 1024)));
+            }
+
+            let mut content = Vec::new();
+            fs::File::open(entry.path())?.read_to_end(&mut content)?;
+
+            files.push(SourceFile {
+                name: path,
+                content: String::from_utf8_lossy(&content).into_owned(),
+            });
+        }
+    }
+
+    Ok(())
+}
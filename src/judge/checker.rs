@@ -0,0 +1,210 @@
+use super::Toolchain;
+use super::sandbox::Status;
+
+/// How far past the tolerance a difference can be and still be accepted,
+/// relative to the tolerance. Numbers read from text are rarely exact, so
+/// an answer right at the tolerance could otherwise be rejected.
+const ROUNDING: f64 = 1e-9;
+
+/// A program written by an admin that's run by the judge.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Program {
+    pub toolchain: Toolchain,
+    pub code: String,
+    pub convention: Convention,
+}
+
+/// How a checker or interactor is run and tells the judge if a solution is
+/// correct.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Convention {
+    /// Like testlib, the program is given the input, expected output and
+    /// output files as arguments and exits with 0 if the output is correct
+    /// and 1 or 2 if it isn't.
+    Testlib,
+    /// Like Kattis output validators, the program is given the input and
+    /// expected output files and a feedback folder as arguments and the
+    /// output on stdin. It exits with 42 if the output is correct and 43 if
+    /// it isn't.
+    Kattis,
+}
+
+impl Convention {
+    /// Every convention an interactor can be stored as with a description.
+    pub const MODES: &'static [(&'static str, &'static str)] = &[
+        ("testlib", "Exits with 0 or 1"),
+        ("kattis", "Kattis validator, exits with 42 or 43"),
+    ];
+
+    /// Gets the convention of a checker or interactor from how it's stored,
+    /// checkers are stored by their mode.
+    pub fn from_mode(mode: &str) -> Self {
+        match mode {
+            "kattis" => Convention::Kattis,
+            _ => Convention::Testlib,
+        }
+    }
+
+    pub fn mode(&self) -> &'static str {
+        match *self {
+            Convention::Testlib => "testlib",
+            Convention::Kattis => "kattis",
+        }
+    }
+
+    /// Whether a program that finished this way found the output correct,
+    /// `None` if the program failed. Anything else is a failure.
+    pub fn verdict(&self, status: Status) -> Option<bool> {
+        match (*self, status) {
+            (Convention::Testlib, Status::Exited(0)) => Some(true),
+            (Convention::Testlib, Status::Exited(1)) | (Convention::Testlib, Status::Exited(2)) => Some(false),
+            (Convention::Kattis, Status::Exited(42)) => Some(true),
+            (Convention::Kattis, Status::Exited(43)) => Some(false),
+            _ => None,
+        }
+    }
+}
+
+/// Decides if the output of a solution is correct.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Checker {
+    /// Lines must match, ignoring trailing whitespace and blank lines.
+    Exact,
+    /// Whitespace separated tokens must match.
+    Tokens,
+    /// Tokens must match ignoring case.
+    CaseInsensitive,
+    /// Tokens must match, numbers within an absolute or relative tolerance.
+    Float(f64),
+    /// A program that decides if the output is correct following its
+    /// convention.
+    Custom(Program),
+}
+
+impl Checker {
+    /// Every mode a checker can be stored as with a description.
+    pub const MODES: &'static [(&'static str, &'static str)] = &[
+        ("exact", "Exact lines"),
+        ("tokens", "Tokens ignoring whitespace"),
+        ("case", "Tokens ignoring case"),
+        ("float", "Tokens with floating point tolerance"),
+        ("custom", "Custom checker program"),
+        ("kattis", "Kattis output validator"),
+    ];
+
+    /// Creates a checker from how it's stored on a problem. This is `None`
+    /// for a custom checker without a program.
+    pub fn from_mode(mode: &str, tolerance: f64, program: Option<Program>) -> Option<Self> {
+        match (mode, program) {
+            ("tokens", _) => Some(Checker::Tokens),
+            ("case", _) => Some(Checker::CaseInsensitive),
+            ("float", _) => Some(Checker::Float(tolerance)),
+            ("custom", Some(program)) | ("kattis", Some(program)) => Some(Checker::Custom(program)),
+            ("custom", None) | ("kattis", None) => None,
+            _ => Some(Checker::Exact),
+        }
+    }
+
+    /// Compares output against the expected output, this is `None` for
+    /// custom checkers since they need to be run by the judge.
+    pub fn compare(&self, expected: &str, actual: &str) -> Option<bool> {
+        match *self {
+            Checker::Exact => Some(lines(expected) == lines(actual)),
+            Checker::Tokens => Some(tokens(expected, actual, |e, a| e == a)),
+            Checker::CaseInsensitive => Some(tokens(expected, actual, |e, a| e.to_lowercase() == a.to_lowercase())),
+            Checker::Float(tolerance) => Some(tokens(expected, actual, |e, a| float(e, a, tolerance))),
+            Checker::Custom(_) => None,
+        }
+    }
+}
+
+fn lines(s: &str) -> Vec<&str> {
+    let mut lines = s.lines()
+        .map(|line| line.trim_end())
+        .collect::<Vec<_>>();
+
+    while lines.last().map(|line| line.is_empty()).unwrap_or(false) {
+        lines.pop();
+    }
+
+    lines
+}
+
+fn tokens<F>(expected: &str, actual: &str, eq: F) -> bool
+    where F: Fn(&str, &str) -> bool
+{
+    let mut expected = expected.split_whitespace();
+    let mut actual = actual.split_whitespace();
+
+    loop {
+        match (expected.next(), actual.next()) {
+            (Some(e), Some(a)) if eq(e, a) => continue,
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+/// Numbers are equal if they're within the tolerance of each other or
+/// within the tolerance relative to the expected number. Infinities only
+/// match themselves and `nan` only matches `nan`. Anything that isn't a
+/// number has to match exactly.
+fn float(expected: &str, actual: &str, tolerance: f64) -> bool {
+    match (expected.parse::<f64>(), actual.parse::<f64>()) {
+        (Ok(e), Ok(a)) if e.is_nan() || a.is_nan() => e.is_nan() && a.is_nan(),
+        (Ok(e), Ok(a)) if e.is_infinite() || a.is_infinite() => e == a,
+        (Ok(e), Ok(a)) => {
+            let diff = (e - a).abs();
+            let tolerance = tolerance * (1.0 + ROUNDING);
+            diff <= tolerance || diff <= tolerance * e.abs()
+        }
+        (Ok(_), Err(_)) => false,
+        _ => expected == actual,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exact_ignores_trailing_whitespace_and_blank_lines() {
+        assert_eq!(Checker::Exact.compare("1 2\n3\n", "1 2  \n3\n\n\n"), Some(true));
+        assert_eq!(Checker::Exact.compare("1 2\n3\n", "1  2\n3\n"), Some(false));
+    }
+
+    #[test]
+    fn float_accepts_differences_up_to_the_tolerance() {
+        let checker = Checker::Float(1e-6);
+        assert_eq!(checker.compare("1", "1.000001"), Some(true));
+        assert_eq!(checker.compare("1", "0.999999"), Some(true));
+        assert_eq!(checker.compare("1", "1.0000011"), Some(false));
+
+        // Large answers are compared relative to the expected number.
+        assert_eq!(checker.compare("1000000", "1000001"), Some(true));
+        assert_eq!(checker.compare("1000000", "1000001.1"), Some(false));
+    }
+
+    #[test]
+    fn float_matches_nan_and_infinity_only_with_themselves() {
+        let checker = Checker::Float(1e-6);
+        assert_eq!(checker.compare("nan", "nan"), Some(true));
+        assert_eq!(checker.compare("inf", "inf"), Some(true));
+        assert_eq!(checker.compare("-inf", "-inf"), Some(true));
+
+        assert_eq!(checker.compare("inf", "-inf"), Some(false));
+        assert_eq!(checker.compare("1", "nan"), Some(false));
+        assert_eq!(checker.compare("nan", "1"), Some(false));
+        assert_eq!(checker.compare("1", "inf"), Some(false));
+        assert_eq!(checker.compare("inf", "1e300"), Some(false));
+    }
+
+    #[test]
+    fn float_compares_words_exactly() {
+        let checker = Checker::Float(1e-6);
+        assert_eq!(checker.compare("YES 0.5", "YES 0.5000001"), Some(true));
+        assert_eq!(checker.compare("YES 0.5", "yes 0.5"), Some(false));
+        assert_eq!(checker.compare("0.5", "half"), Some(false));
+        assert_eq!(checker.compare("0.5", "0.5 0.5"), Some(false));
+    }
+}
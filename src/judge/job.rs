@@ -0,0 +1,200 @@
+use chrono::Utc;
+use diesel::{ self, prelude::* };
+use failure::{ self, Error };
+
+use ::db::{
+    models::{ Language, Problem, Solution, SolutionFile, TestCase },
+    schema::{ language, problem, problem_language, solution, solution_file, test_case, test_result },
+};
+use super::{ Checker, Convention, Judge, Limits, Package, Program, Report, SourceFile, Test, Toolchain, Verdict };
+
+/// Everything needed to judge a solution. It's loaded up front so the
+/// database isn't needed while judging.
+#[derive(Clone, Debug)]
+pub struct Job {
+    pub sid: i32,
+    pub pid: i32,
+    /// The id of the solution's language.
+    pub lid: i32,
+    pub language: String,
+    /// `None` if the solution's language can't be judged.
+    pub toolchain: Option<Toolchain>,
+    pub files: Vec<SourceFile>,
+    pub package: Package,
+}
+
+impl Job {
+    pub fn load(db: &SqliteConnection, sid: i32) -> Result<Self, Error> {
+        let (solution, language) = solution::table
+            .inner_join(language::table)
+            .filter(solution::id.eq(sid))
+            .first::<(Solution, Language)>(db)?;
+
+        let files = load_files(db, &solution, &language)?;
+
+        Ok(Job {
+            sid: sid,
+            pid: solution.pid,
+            lid: solution.language,
+            toolchain: Toolchain::for_language(&language),
+            language: language.name,
+            files: files,
+            package: load_package(db, solution.pid, solution.language)?,
+        })
+    }
+
+    pub fn run(&self, judge: &Judge) -> Result<Report, Error> {
+        let toolchain = self.toolchain.as_ref()
+            .ok_or_else(|| failure::err_msg(format!("{} can't be judged.", self.language)))?;
+
+        judge.judge(toolchain, &self.files, &self.package)
+    }
+}
+
+/// Loads the files of a solution.
+pub fn load_files(db: &SqliteConnection, solution: &Solution, language: &Language) -> QueryResult<Vec<SourceFile>> {
+    let mut files = solution_file::table
+        .filter(solution_file::sid.eq(solution.id))
+        .order(solution_file::name.asc())
+        .load::<SolutionFile>(db)?
+        .into_iter()
+        .map(|file| SourceFile { name: file.name, content: file.content })
+        .collect::<Vec<_>>();
+
+    // Solutions with a single file keep it in their code.
+    if files.is_empty() {
+        files.push(SourceFile { name: language.source.clone(), content: solution.code.clone() });
+    }
+
+    Ok(files)
+}
+
+/// Loads a problem's tests, how they're checked and its limits for
+/// solutions in a language.
+pub fn load_package(db: &SqliteConnection, pid: i32, lid: i32) -> Result<Package, Error> {
+    let problem = problem::table
+        .filter(problem::id.eq(pid))
+        .first::<Problem>(db)?;
+
+    let tests = test_case::table
+        .filter(test_case::pid.eq(pid))
+        .order(test_case::position.asc())
+        .load::<TestCase>(db)?
+        .into_iter()
+        .map(|test| Test { input: test.input, output: test.output, sample: test.sample })
+        .collect();
+
+    let convention = Convention::from_mode(&problem.checker);
+    let program = load_program(db, problem.checker_language, problem.checker_code, convention)?;
+    let checker = Checker::from_mode(&problem.checker, problem.tolerance, program)
+        .ok_or_else(|| failure::err_msg("The custom checker's language can't be judged."))?;
+
+    let interactor = if problem.interactive {
+        let convention = Convention::from_mode(&problem.interactor_convention);
+        let program = load_program(db, problem.interactor_language, problem.interactor_code, convention)?
+            .ok_or_else(|| failure::err_msg("The interactor's language can't be judged."))?;
+        Some(program)
+    } else {
+        None
+    };
+
+    Ok(Package {
+        tests: tests,
+        limits: load_limits(db, &problem, lid)?,
+        checker: checker,
+        interactor: interactor,
+    })
+}
+
+/// Gets a problem's limits for solutions in a language. Some languages are
+/// given more time than others.
+pub fn load_limits(db: &SqliteConnection, problem: &Problem, lid: i32) -> QueryResult<Limits> {
+    let multiplier = problem_language::table
+        .find((problem.id, lid))
+        .select(problem_language::time_multiplier)
+        .first::<f64>(db)
+        .optional()?
+        .unwrap_or(1.0);
+
+    let limits = Limits::new(problem.time_limit as u64, problem.memory_limit as u64, problem.output_limit as u64);
+    Ok(limits.scale_time(multiplier))
+}
+
+/// Loads a program written by an admin if its language can be judged.
+fn load_program(db: &SqliteConnection, lid: Option<i32>, code: String, convention: Convention) -> QueryResult<Option<Program>> {
+    let language = match lid {
+        Some(lid) => language::table
+            .filter(language::id.eq(lid))
+            .first::<Language>(db)
+            .optional()?,
+        None => None,
+    };
+
+    Ok(language
+        .and_then(|language| Toolchain::for_language(&language))
+        .map(|toolchain| Program { toolchain: toolchain, code: code, convention: convention }))
+}
+
+/// Saves the verdict of a solution and how it did on each test.
+pub fn store_report(db: &SqliteConnection, sid: i32, report: &Report) -> QueryResult<()> {
+    let (runtime, memory) = match report.verdict {
+        Verdict::CompileError => (None, None),
+        _ => (Some(report.time as i32), Some(report.memory as i32)),
+    };
+
+    diesel::update(solution::table.filter(solution::id.eq(sid)))
+        .set((
+            solution::verdict.eq(report.verdict.code()),
+            solution::score.eq(report.score()),
+            solution::runtime.eq(runtime),
+            solution::memory.eq(memory),
+            solution::message.eq(&report.message),
+            solution::judged_at.eq(Utc::now().naive_utc()),
+        ))
+        .execute(db)?;
+
+    diesel::delete(test_result::table.filter(test_result::sid.eq(sid)))
+        .execute(db)?;
+
+    for (i, test) in report.tests.iter().enumerate() {
+        let sample = test.sample.as_ref();
+
+        diesel::insert_into(test_result::table)
+            .values((
+                test_result::sid.eq(sid),
+                test_result::position.eq(i as i32 + 1),
+                test_result::verdict.eq(test.verdict.code()),
+                test_result::runtime.eq(test.time as i32),
+                test_result::memory.eq(test.memory as i32),
+                test_result::message.eq(&test.message),
+                test_result::stderr.eq(&test.stderr),
+                test_result::input.eq(sample.map(|sample| &sample.input)),
+                test_result::expected.eq(sample.map(|sample| &sample.expected)),
+                test_result::output.eq(sample.map(|sample| &sample.output)),
+                test_result::hidden.eq(test.hidden),
+            ))
+            .execute(db)?;
+    }
+
+    Ok(())
+}
+
+/// Marks a solution that couldn't be judged as a judge error with the
+/// reason in its message.
+pub fn store_error(db: &SqliteConnection, sid: i32, error: &str) -> QueryResult<()> {
+    diesel::update(solution::table.filter(solution::id.eq(sid)))
+        .set((
+            solution::verdict.eq(Verdict::JudgeError.code()),
+            solution::score.eq(0),
+            solution::runtime.eq(None::<i32>),
+            solution::memory.eq(None::<i32>),
+            solution::message.eq(error),
+            solution::judged_at.eq(Utc::now().naive_utc()),
+        ))
+        .execute(db)?;
+
+    diesel::delete(test_result::table.filter(test_result::sid.eq(sid)))
+        .execute(db)?;
+
+    Ok(())
+}
@@ -0,0 +1,617 @@
+//! The judge compiles a solution with its language's toolchain and runs it
+//! against a problem's tests inside of a sandbox.
+
+use std::io;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::thread;
+
+use failure::{ self, Error };
+
+use ::db::models::Language;
+
+pub use self::archive::{ MAX_FILES, MAX_PACKAGE_SIZE, MAX_SIZE, is_archive, valid_name };
+pub use self::checker::{ Checker, Convention, Program };
+pub use self::job::{ Job, load_files, load_limits, load_package, store_error, store_report };
+pub use self::sandbox::{ Execution, Limits, Sandbox, Status, pipe };
+
+mod archive;
+mod checker;
+mod job;
+pub mod node;
+pub mod nodes;
+pub mod protocol;
+pub mod queue;
+pub mod reference;
+mod sandbox;
+mod wasm;
+
+const COMPILE_LOG: &str = "compile.log";
+const INPUT: &str = "input.txt";
+const OUTPUT: &str = "output.txt";
+const ERROR: &str = "error.txt";
+const ANSWER: &str = "answer.txt";
+const FEEDBACK: &str = "feedback.txt";
+
+/// Where Kattis validators write their feedback, the message for the
+/// solution's author is kept in `TEAM_MESSAGE`.
+const FEEDBACK_DIR: &str = "feedback";
+const TEAM_MESSAGE: &str = "feedback/teammessage.txt";
+
+/// How much of the compiler output is kept for the judge message.
+const MESSAGE_LIMIT: u64 = 4 * 1024;
+
+/// How much output is kept when running code on its own.
+const RUN_OUTPUT_LIMIT: u64 = 64 * 1024;
+
+/// How much of a solution's stderr is kept for each test.
+const STDERR_LIMIT: u64 = 1024;
+
+/// How much of a solution's output is kept for sample tests.
+const SAMPLE_OUTPUT_LIMIT: u64 = 16 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Verdict {
+    /// The solution hasn't been judged yet.
+    Pending,
+    Accepted,
+    WrongAnswer,
+    TimeLimitExceeded,
+    MemoryLimitExceeded,
+    RuntimeError,
+    CompileError,
+    /// The judge failed, the solution has to be rejudged once it's fixed.
+    JudgeError,
+}
+
+impl Verdict {
+    /// The short code for the verdict, this is how verdicts are stored.
+    pub fn code(&self) -> &'static str {
+        match *self {
+            Verdict::Pending => "PD",
+            Verdict::Accepted => "AC",
+            Verdict::WrongAnswer => "WA",
+            Verdict::TimeLimitExceeded => "TLE",
+            Verdict::MemoryLimitExceeded => "MLE",
+            Verdict::RuntimeError => "RE",
+            Verdict::CompileError => "CE",
+            Verdict::JudgeError => "JE",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        match code {
+            "PD" => Some(Verdict::Pending),
+            "AC" => Some(Verdict::Accepted),
+            "WA" => Some(Verdict::WrongAnswer),
+            "TLE" => Some(Verdict::TimeLimitExceeded),
+            "MLE" => Some(Verdict::MemoryLimitExceeded),
+            "RE" => Some(Verdict::RuntimeError),
+            "CE" => Some(Verdict::CompileError),
+            "JE" => Some(Verdict::JudgeError),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Verdict::Pending => "Pending",
+            Verdict::Accepted => "Accepted",
+            Verdict::WrongAnswer => "Wrong Answer",
+            Verdict::TimeLimitExceeded => "Time Limit Exceeded",
+            Verdict::MemoryLimitExceeded => "Memory Limit Exceeded",
+            Verdict::RuntimeError => "Runtime Error",
+            Verdict::CompileError => "Compile Error",
+            Verdict::JudgeError => "Judge Error",
+        }
+    }
+}
+
+/// What runs a solution once it's compiled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Backend {
+    /// The run command is a shell command run as a process in the sandbox.
+    Native,
+    /// The run command names a WASI module in the sandbox, followed by its
+    /// arguments, that's run in an embedded WebAssembly runtime.
+    Wasi,
+}
+
+impl Backend {
+    /// Every backend a language can be stored with and a description.
+    pub const NAMES: &'static [(&'static str, &'static str)] = &[
+        ("native", "Native process"),
+        ("wasi", "WebAssembly (WASI)"),
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "native" => Some(Backend::Native),
+            "wasi" => Some(Backend::Wasi),
+            _ => None,
+        }
+    }
+}
+
+/// The commands used to build and run a solution.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Toolchain {
+    /// The file name the solution is written to.
+    pub source: String,
+    /// Compiling always happens natively, even for WebAssembly languages.
+    pub compile: Option<String>,
+    pub run: String,
+    pub backend: Backend,
+}
+
+impl Toolchain {
+    /// Gets the toolchain of a language if it can be judged.
+    pub fn for_language(language: &Language) -> Option<Self> {
+        if !language.enabled || language.source.is_empty() || language.run.is_empty() {
+            return None;
+        }
+
+        let compile = match language.compile.trim() {
+            "" => None,
+            compile => Some(compile.into()),
+        };
+
+        Some(Toolchain {
+            source: language.source.clone(),
+            compile: compile,
+            run: language.run.clone(),
+            backend: Backend::from_name(&language.backend)?,
+        })
+    }
+}
+
+/// One of the files of a solution. The name is a path relative to the
+/// directory the solution is built in.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SourceFile {
+    pub name: String,
+    pub content: String,
+}
+
+/// An input to give a solution and the output it's expected to produce.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Test {
+    pub input: String,
+    pub output: String,
+    /// Sample tests are shown to users so their results can show more.
+    pub sample: bool,
+}
+
+/// Everything about a problem the judge needs to judge solutions to it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Package {
+    pub tests: Vec<Test>,
+    /// The limits for the language being judged.
+    pub limits: Limits,
+    pub checker: Checker,
+    /// Interactive problems have a program the solution talks to, it reads
+    /// the test's input and decides if the solution is correct.
+    pub interactor: Option<Program>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TestResult {
+    pub verdict: Verdict,
+    /// CPU time in milliseconds.
+    pub time: u64,
+    /// Peak memory in kilobytes.
+    pub memory: u64,
+    pub message: String,
+    /// The start of what the solution wrote to stderr.
+    pub stderr: String,
+    /// The test and the solution's output if it's a sample test.
+    pub sample: Option<Sample>,
+    /// If the test isn't a sample, so its stderr is only shown to admins.
+    pub hidden: bool,
+}
+
+/// A sample test and what the solution output for it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Sample {
+    pub input: String,
+    pub expected: String,
+    pub output: String,
+}
+
+/// The result of running code once with a given input.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Run {
+    /// The compiler output if compiling failed.
+    pub compile_error: Option<String>,
+    pub execution: Option<Execution>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Report {
+    pub verdict: Verdict,
+    /// The longest time taken by any test.
+    pub time: u64,
+    /// The most memory used by any test.
+    pub memory: u64,
+    pub message: String,
+    pub tests: Vec<TestResult>,
+}
+
+impl Report {
+    fn compile_error(message: String) -> Self {
+        Report {
+            verdict: Verdict::CompileError,
+            time: 0,
+            memory: 0,
+            message: message,
+            tests: Vec::new(),
+        }
+    }
+
+    fn new(tests: Vec<TestResult>) -> Self {
+        // The verdict of the solution is the verdict of the first test that
+        // wasn't accepted.
+        let failed = tests.iter()
+            .enumerate()
+            .find(|&(_, test)| test.verdict != Verdict::Accepted);
+
+        let (verdict, message) = match failed {
+            Some((i, test)) => (test.verdict, format!("Test {}: {} {}", i + 1, test.verdict.name(), test.message)),
+            None => (Verdict::Accepted, String::new()),
+        };
+
+        Report {
+            verdict: verdict,
+            time: tests.iter().map(|test| test.time).max().unwrap_or(0),
+            memory: tests.iter().map(|test| test.memory).max().unwrap_or(0),
+            message: message.trim().into(),
+            tests: tests,
+        }
+    }
+
+    /// The number of tests that were accepted.
+    pub fn passed(&self) -> usize {
+        self.tests.iter()
+            .filter(|test| test.verdict == Verdict::Accepted)
+            .count()
+    }
+
+    /// The percentage of tests that were accepted.
+    pub fn score(&self) -> i32 {
+        if self.tests.is_empty() {
+            0
+        } else {
+            (self.passed() * 100 / self.tests.len()) as i32
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Judge {
+    /// Sandboxes are created in this directory.
+    root: PathBuf,
+}
+
+impl Judge {
+    pub fn new<P>(root: P) -> Self
+        where P: Into<PathBuf>
+    {
+        Judge {
+            root: root.into(),
+        }
+    }
+
+    /// Compiles the solution's files and runs it against every test. Errors
+    /// are only returned if the judge itself fails, problems with the
+    /// solution are reported in the verdict.
+    pub fn judge(&self, toolchain: &Toolchain, files: &[SourceFile], package: &Package) -> Result<Report, Error> {
+        if package.tests.is_empty() {
+            return Err(failure::err_msg("There are no tests to judge against."));
+        }
+
+        // Custom checkers and interactors are built in their own sandbox.
+        // Programs can only see the sandbox they run in, so solutions can't
+        // read them or the expected outputs given to them.
+        let checker = match package.checker {
+            Checker::Custom(ref program) if package.interactor.is_none() => Some(self.build(program, "checker")?),
+            _ => None,
+        };
+
+        let interactor = match package.interactor {
+            Some(ref program) => Some((program, self.build(program, "interactor")?)),
+            None => None,
+        };
+
+        let sandbox = Sandbox::new(&self.root)?;
+        for file in files {
+            sandbox.write(&file.name, &file.content)?;
+        }
+
+        if let Some(log) = self.compile(&sandbox, toolchain)? {
+            return Ok(Report::compile_error(log));
+        }
+
+        // Every test starts from a fresh copy of the compiled solution, so
+        // nothing it writes during one test is there for the next. Each run
+        // already gets an empty `/tmp`.
+        let results = package.tests.iter()
+            .map(|test| {
+                let run = sandbox.duplicate(&self.root)?;
+                match interactor {
+                    Some((program, ref interactor)) => self.interact(&run, toolchain, &package.limits, program, interactor, test),
+                    None => self.run_test(&run, toolchain, package, checker.as_ref(), test),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Report::new(results))
+    }
+
+    /// Compiles and runs the code once with the given input.
+    pub fn execute(&self, toolchain: &Toolchain, code: &str, input: &str, limits: &Limits) -> Result<Run, Error> {
+        let sandbox = Sandbox::new(&self.root)?;
+        sandbox.write(&toolchain.source, code)?;
+
+        if let Some(log) = self.compile(&sandbox, toolchain)? {
+            return Ok(Run {
+                compile_error: Some(log),
+                execution: None,
+                stdout: String::new(),
+                stderr: String::new(),
+            });
+        }
+
+        sandbox.write(INPUT, input)?;
+        let exec = self.run_solution(&sandbox, toolchain, limits)?;
+
+        Ok(Run {
+            compile_error: None,
+            execution: Some(exec),
+            stdout: sandbox.read(OUTPUT, RUN_OUTPUT_LIMIT)?,
+            stderr: sandbox.read(ERROR, MESSAGE_LIMIT)?,
+        })
+    }
+
+    /// Compiles the solution in the sandbox if the toolchain needs to,
+    /// returning the compiler output if it failed.
+    fn compile(&self, sandbox: &Sandbox, toolchain: &Toolchain) -> Result<Option<String>, Error> {
+        let compile = match toolchain.compile {
+            Some(ref compile) => compile,
+            None => return Ok(None),
+        };
+
+        let exec = sandbox.run(compile, &Limits::compile(), None, COMPILE_LOG, COMPILE_LOG)?;
+
+        if exec.success() {
+            Ok(None)
+        } else {
+            let mut log = sandbox.read(COMPILE_LOG, MESSAGE_LIMIT)?;
+            if exec.status == Status::TimedOut {
+                log.push_str("\nCompilation timed out.");
+            }
+            Ok(Some(log))
+        }
+    }
+
+    /// Runs a compiled solution with `INPUT` as its stdin, writing its stdout
+    /// and stderr to `OUTPUT` and `ERROR`.
+    fn run_solution(&self, sandbox: &Sandbox, toolchain: &Toolchain, limits: &Limits) -> Result<Execution, Error> {
+        match toolchain.backend {
+            Backend::Native => Ok(sandbox.run(&toolchain.run, limits, Some(INPUT), OUTPUT, ERROR)?),
+            Backend::Wasi => wasm::run(sandbox, &toolchain.run, limits, Some(INPUT), OUTPUT, ERROR),
+        }
+    }
+
+    /// Builds a program written by an admin in its own sandbox. Programs are
+    /// run with their inputs as arguments, so they have to be native.
+    fn build(&self, program: &Program, name: &str) -> Result<Sandbox, Error> {
+        if program.toolchain.backend != Backend::Native {
+            return Err(failure::err_msg(format!("The {} has to be written in a language that runs natively.", name)));
+        }
+
+        let sandbox = Sandbox::new(&self.root)?;
+        sandbox.write(&program.toolchain.source, &program.code)?;
+
+        match self.compile(&sandbox, &program.toolchain)? {
+            Some(log) => Err(failure::err_msg(format!("The {} failed to compile: {}", name, log))),
+            None => Ok(sandbox),
+        }
+    }
+
+    fn run_test(&self, sandbox: &Sandbox, toolchain: &Toolchain, package: &Package, checker: Option<&Sandbox>, test: &Test) -> Result<TestResult, Error> {
+        let limits = &package.limits;
+        sandbox.write(INPUT, &test.input)?;
+        let exec = self.run_solution(sandbox, toolchain, limits)?;
+
+        let mut message = String::new();
+
+        let verdict = if exec.time_exceeded(limits) {
+            Verdict::TimeLimitExceeded
+        } else if exec.memory_exceeded(limits) {
+            Verdict::MemoryLimitExceeded
+        } else if !exec.success() {
+            message = exec.describe();
+            Verdict::RuntimeError
+        } else {
+            let output = sandbox.read(OUTPUT, limits.output * 1024)?;
+
+            let correct = match (package.checker.compare(&test.output, &output), checker) {
+                (Some(correct), _) => correct,
+                (None, Some(checker)) => {
+                    let (correct, feedback) = self.check(checker, &package.checker, test, &output)?;
+                    message = feedback;
+                    correct
+                }
+                (None, None) => return Err(failure::err_msg("The checker wasn't built.")),
+            };
+
+            if correct {
+                Verdict::Accepted
+            } else {
+                Verdict::WrongAnswer
+            }
+        };
+
+        let sample = if test.sample {
+            Some(Sample {
+                input: test.input.clone(),
+                expected: test.output.clone(),
+                output: sandbox.read(OUTPUT, SAMPLE_OUTPUT_LIMIT)?,
+            })
+        } else {
+            None
+        };
+
+        Ok(TestResult {
+            verdict: verdict,
+            time: exec.time,
+            memory: exec.memory,
+            message: message,
+            stderr: sandbox.read(ERROR, STDERR_LIMIT)?,
+            sample: sample,
+            hidden: !test.sample,
+        })
+    }
+
+    /// Runs a test of an interactive problem. The solution's output is piped
+    /// to the interactor and the interactor's output is piped back to the
+    /// solution. The interactor is given the test's input and expected output
+    /// files and decides if the solution is correct like a custom checker.
+    fn interact(&self, sandbox: &Sandbox, toolchain: &Toolchain, limits: &Limits, program: &Program, interactor: &Sandbox, test: &Test) -> Result<TestResult, Error> {
+        if toolchain.backend != Backend::Native {
+            return Err(failure::err_msg("Interactive problems can't be judged in WebAssembly."));
+        }
+
+        interactor.write(INPUT, &test.input)?;
+        interactor.write(ANSWER, &test.output)?;
+
+        let (solution_in, interactor_out) = pipe()?;
+        let (interactor_in, solution_out) = pipe()?;
+
+        let cmd = match program.convention {
+            Convention::Testlib => format!("{} {} {}", program.toolchain.run, INPUT, ANSWER),
+            Convention::Kattis => {
+                interactor.create_dir(FEEDBACK_DIR)?;
+                format!("{} {} {} {}", program.toolchain.run, INPUT, ANSWER, FEEDBACK_DIR)
+            }
+        };
+        let judge = interactor.spawn(
+            &cmd,
+            &Limits::compile(),
+            Stdio::from(interactor_in),
+            Stdio::from(interactor_out),
+            Stdio::from(interactor.create(FEEDBACK)?),
+        )?;
+
+        let solution = sandbox.spawn(
+            &toolchain.run,
+            limits,
+            Stdio::from(solution_in),
+            Stdio::from(solution_out),
+            Stdio::from(sandbox.create(ERROR)?),
+        );
+
+        let solution = match solution {
+            Ok(solution) => solution,
+            Err(err) => {
+                judge.kill();
+                return Err(err.into());
+            }
+        };
+
+        // Both have to be waited on at the same time since either could be
+        // the one to stop first.
+        let judge = thread::spawn(move || judge.wait());
+        let exec = solution.wait()?;
+        let judged = judge.join()
+            .map_err(|_| failure::err_msg("The interactor's thread panicked."))??;
+
+        let feedback = feedback(interactor, program)?;
+        let mut message = String::new();
+
+        // The solution going over its limits is reported before anything
+        // the interactor says since the interactor will just see it stop.
+        let verdict = if exec.time_exceeded(limits) {
+            Verdict::TimeLimitExceeded
+        } else if exec.memory_exceeded(limits) {
+            Verdict::MemoryLimitExceeded
+        } else {
+            match program.convention.verdict(judged.status) {
+                Some(false) => {
+                    message = feedback;
+                    Verdict::WrongAnswer
+                }
+                Some(true) if exec.success() => Verdict::Accepted,
+                Some(true) => {
+                    message = exec.describe();
+                    Verdict::RuntimeError
+                }
+                None => {
+                    let log = interactor.read(FEEDBACK, MESSAGE_LIMIT)?;
+                    return Err(failure::err_msg(format!("The interactor failed. {} {}", judged.describe(), log)));
+                }
+            }
+        };
+
+        // The solution's output went to the interactor so there's nothing to
+        // show for samples.
+        Ok(TestResult {
+            verdict: verdict,
+            time: exec.time,
+            memory: exec.memory,
+            message: message,
+            stderr: sandbox.read(ERROR, STDERR_LIMIT)?,
+            sample: None,
+            hidden: !test.sample,
+        })
+    }
+
+    /// Runs a custom checker on the output of a test, returning if the
+    /// output was correct and any feedback the checker wrote.
+    fn check(&self, sandbox: &Sandbox, checker: &Checker, test: &Test, output: &str) -> Result<(bool, String), Error> {
+        let program = match *checker {
+            Checker::Custom(ref program) => program,
+            _ => return Err(failure::err_msg("Not a custom checker.")),
+        };
+
+        sandbox.write(INPUT, &test.input)?;
+        sandbox.write(ANSWER, &test.output)?;
+        sandbox.write(OUTPUT, output)?;
+
+        let exec = match program.convention {
+            Convention::Testlib => {
+                let cmd = format!("{} {} {} {}", program.toolchain.run, INPUT, ANSWER, OUTPUT);
+                sandbox.run(&cmd, &Limits::compile(), None, FEEDBACK, FEEDBACK)?
+            }
+            Convention::Kattis => {
+                sandbox.create_dir(FEEDBACK_DIR)?;
+                let cmd = format!("{} {} {} {}", program.toolchain.run, INPUT, ANSWER, FEEDBACK_DIR);
+                sandbox.run(&cmd, &Limits::compile(), Some(OUTPUT), FEEDBACK, FEEDBACK)?
+            }
+        };
+
+        match program.convention.verdict(exec.status) {
+            Some(correct) => Ok((correct, feedback(sandbox, program)?)),
+            None => {
+                let log = sandbox.read(FEEDBACK, MESSAGE_LIMIT)?;
+                Err(failure::err_msg(format!("The checker failed. {} {}", exec.describe(), log)))
+            }
+        }
+    }
+}
+
+/// Reads the feedback a checker or interactor left for the solution's
+/// author. Kattis validators write it to a file, anything they print is
+/// only meant for admins.
+fn feedback(sandbox: &Sandbox, program: &Program) -> Result<String, Error> {
+    match program.convention {
+        Convention::Testlib => Ok(sandbox.read(FEEDBACK, MESSAGE_LIMIT)?),
+        Convention::Kattis => match sandbox.read(TEAM_MESSAGE, MESSAGE_LIMIT) {
+            Ok(message) => Ok(message),
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(String::new()),
+            Err(err) => Err(err.into()),
+        },
+    }
+}
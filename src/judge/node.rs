@@ -0,0 +1,258 @@
+//! A judge node gets its work from a server over HTTP instead of from the
+//! database, so judging can happen on a machine of its own. It judges with
+//! the same code as the server's own workers.
+
+use std::collections::HashMap;
+use std::sync::{ Arc, Mutex };
+use std::thread;
+use std::time::Duration;
+
+use failure::{ self, Error };
+use reqwest::{ self, Response, StatusCode, header::Authorization };
+use serde::{ Serialize, de::DeserializeOwned };
+
+use super::{ Job, Judge, Package };
+use super::protocol::{
+    PREFIX, VERSION,
+    Fetch, GiveBack, Heartbeat, Judged, PackageRef, PackageUpload, Ran, Register, Registered, Saved, Work,
+};
+
+/// How long an idle worker waits before asking for work again.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long to wait before trying again when the server can't be reached.
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The most packages kept around, they're all dropped when there are more.
+const MAX_PACKAGES: usize = 32;
+
+#[derive(Clone)]
+pub struct Config {
+    /// Where the server can be reached, such as `http://127.0.0.1:8080`.
+    pub server: String,
+    /// The token shared with the server.
+    pub token: String,
+    pub name: String,
+    pub workers: usize,
+}
+
+/// Talks to the server for a node.
+#[derive(Clone)]
+struct Client {
+    config: Config,
+    http: reqwest::Client,
+    packages: Arc<Mutex<HashMap<String, Package>>>,
+}
+
+impl Client {
+    fn send<T>(&self, path: &str, body: &T) -> Result<Response, Error>
+        where T: Serialize
+    {
+        let url = format!("{}{}/{}", self.config.server.trim_right_matches('/'), PREFIX, path);
+        Ok(self.http.post(url.as_str())
+            .header(Authorization(format!("Bearer {}", self.config.token)))
+            .json(body)
+            .send()?)
+    }
+
+    fn post<T, R>(&self, path: &str, body: &T) -> Result<R, Error>
+        where T: Serialize, R: DeserializeOwned
+    {
+        let resp = self.send(path, body)?;
+        if resp.status() == StatusCode::NotFound {
+            return Err(failure::err_msg("The server doesn't know this node."));
+        }
+
+        Ok(resp.error_for_status()
+            .and_then(|mut resp| resp.json::<R>())?)
+    }
+
+    fn get<R>(&self, path: &str) -> Result<R, Error>
+        where R: DeserializeOwned
+    {
+        let url = format!("{}{}/{}", self.config.server.trim_right_matches('/'), PREFIX, path);
+        Ok(self.http.get(url.as_str())
+            .header(Authorization(format!("Bearer {}", self.config.token)))
+            .send()
+            .and_then(|resp| resp.error_for_status())
+            .and_then(|mut resp| resp.json::<R>())?)
+    }
+
+    fn register(&self) -> Result<Registered, Error> {
+        self.post("register", &Register {
+            node: self.config.name.clone(),
+            version: VERSION,
+            workers: self.config.workers as i32,
+        })
+    }
+
+    /// Sends a heartbeat, returning false if the server doesn't know the
+    /// node.
+    fn heartbeat(&self) -> Result<bool, Error> {
+        let resp = self.send("heartbeat", &Heartbeat { node: self.config.name.clone() })?;
+        if resp.status() == StatusCode::NotFound {
+            return Ok(false);
+        }
+
+        resp.error_for_status()?;
+        Ok(true)
+    }
+
+    /// Gets a package from the cache, downloading it if the node doesn't
+    /// have the version that's needed.
+    fn package(&self, package: &PackageRef) -> Result<Package, Error> {
+        let cached = self.packages.lock()
+            .ok()
+            .and_then(|packages| packages.get(&package.digest).cloned());
+
+        if let Some(cached) = cached {
+            return Ok(cached);
+        }
+
+        let upload = self.get::<PackageUpload>(&format!("packages/{}/{}", package.pid, package.lid))?;
+        if let Ok(mut packages) = self.packages.lock() {
+            if packages.len() >= MAX_PACKAGES {
+                packages.clear();
+            }
+            packages.insert(upload.digest, upload.package.clone());
+        }
+
+        Ok(upload.package)
+    }
+
+    /// Hands a solution back to the server to be judged by someone else,
+    /// trying until the server can be reached. Nothing else would take the
+    /// claim back while the node is still around.
+    fn give_back(&self, worker: &str, entry: i32) {
+        let msg = GiveBack { worker: worker.into(), entry: entry };
+        while let Err(err) = self.post::<_, Saved>("giveback", &msg) {
+            error!("{} couldn't give back its work: {}", worker, err);
+            thread::sleep(RETRY_INTERVAL);
+        }
+    }
+}
+
+/// Registers with the server and judges whatever it hands out, forever.
+pub fn run(config: Config, judge: Judge) -> Result<(), Error> {
+    let client = Client {
+        config: config,
+        http: reqwest::Client::new(),
+        packages: Arc::new(Mutex::new(HashMap::new())),
+    };
+
+    let registered = client.register()?;
+    info!("Registered {} with {}", client.config.name, client.config.server);
+
+    let mut threads = Vec::new();
+
+    let heartbeat = client.clone();
+    threads.push(thread::Builder::new()
+        .name("heartbeat".into())
+        .spawn(move || beat(&heartbeat, Duration::from_secs(registered.heartbeat)))?);
+
+    for i in 0..client.config.workers {
+        let name = format!("{}/{}", client.config.name, i);
+        let client = client.clone();
+        let judge = judge.clone();
+
+        threads.push(thread::Builder::new()
+            .name(name.clone())
+            .spawn(move || work(&name, &client, &judge))?);
+    }
+
+    for thread in threads {
+        let _ = thread.join();
+    }
+
+    Ok(())
+}
+
+/// Lets the server know the node is still around, registering again if the
+/// server has forgotten about it.
+fn beat(client: &Client, interval: Duration) {
+    loop {
+        thread::sleep(interval);
+
+        match client.heartbeat() {
+            Ok(true) => {}
+            Ok(false) => {
+                warn!("The server forgot about this node, registering again");
+                if let Err(err) = client.register() {
+                    error!("Couldn't register with the server: {}", err);
+                }
+            }
+            Err(err) => warn!("Couldn't send a heartbeat: {}", err),
+        }
+    }
+}
+
+fn work(name: &str, client: &Client, judge: &Judge) {
+    let fetch = Fetch { worker: name.into() };
+
+    loop {
+        let work = match client.post::<_, Work>("work", &fetch) {
+            Ok(work) => work,
+            Err(err) => {
+                error!("{} couldn't get work: {}", name, err);
+                thread::sleep(RETRY_INTERVAL);
+                continue;
+            }
+        };
+
+        let report = match work {
+            Work::Idle => {
+                thread::sleep(POLL_INTERVAL);
+                continue;
+            }
+            Work::Run { id, task } => {
+                let result = task.run(judge).map_err(|err| err.to_string());
+                if let Err(ref err) = result {
+                    warn!("{} couldn't run code: {}", name, err);
+                }
+
+                client.post::<_, Saved>("ran", &Ran { worker: name.into(), id: id, result: result })
+                    .map(|_| ())
+            }
+            Work::Judge { entry, sid, language, toolchain, files, package } => {
+                // Not getting the package isn't the solution's fault, so it's
+                // given back to be judged once the server can be reached.
+                let loaded = match client.package(&package) {
+                    Ok(loaded) => loaded,
+                    Err(err) => {
+                        warn!("{} couldn't get the package for solution {}: {}", name, sid, err);
+                        client.give_back(name, entry);
+                        thread::sleep(RETRY_INTERVAL);
+                        continue;
+                    }
+                };
+
+                let job = Job {
+                    sid: sid,
+                    pid: package.pid,
+                    lid: package.lid,
+                    language: language,
+                    toolchain: toolchain,
+                    files: files,
+                    package: loaded,
+                };
+
+                let result = job.run(judge).map_err(|err| err.to_string());
+
+                if let Err(ref err) = result {
+                    warn!("{} couldn't judge solution {}: {}", name, sid, err);
+                }
+
+                client.post::<_, Saved>("judged", &Judged { worker: name.into(), entry: entry, result: result })
+                    .map(|saved| if saved.saved {
+                        info!("{} judged solution {}", name, sid);
+                    } else {
+                        info!("{} dropped solution {}, it was queued again", name, sid);
+                    })
+            }
+        };
+
+        if let Err(err) = report {
+            error!("{} couldn't send its result: {}", name, err);
+        }
+    }
+}
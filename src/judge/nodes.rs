@@ -0,0 +1,90 @@
+//! Keeps track of the judge nodes working for the server. Nodes that stop
+//! sending heartbeats are dropped and whatever they were doing is handed to
+//! another worker.
+
+use std::thread;
+use std::time::Duration as StdDuration;
+
+use chrono::{ Duration, Utc };
+use diesel::{ self, prelude::* };
+
+use ::db::schema::judge_node;
+use super::queue::{ Dispatcher, LOCAL_PREFIX };
+
+/// How often nodes send a heartbeat.
+pub const HEARTBEAT_INTERVAL: StdDuration = StdDuration::from_secs(10);
+
+/// Nodes that haven't been heard from in this many seconds are dropped.
+const NODE_TIMEOUT: i64 = 60;
+
+/// Checks that a node's name can be used in the names of its workers.
+pub fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 64
+        && !name.starts_with(LOCAL_PREFIX)
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+/// The prefix of the names of a node's workers.
+pub fn worker_prefix(node: &str) -> String {
+    format!("{}/", node)
+}
+
+/// Gets the node a worker belongs to.
+pub fn node_of(worker: &str) -> &str {
+    worker.split('/').next().unwrap_or(worker)
+}
+
+/// Adds a node to the registry. A node registering again has restarted, so
+/// whatever it was doing before is given to someone else.
+pub fn register(db: &SqliteConnection, dispatcher: &Dispatcher, name: &str, workers: i32) -> QueryResult<()> {
+    dispatcher.release(db, &worker_prefix(name))?;
+
+    diesel::replace_into(judge_node::table)
+        .values((
+            judge_node::name.eq(name),
+            judge_node::workers.eq(workers),
+            judge_node::seen_at.eq(Utc::now().naive_utc()),
+        ))
+        .execute(db)?;
+
+    Ok(())
+}
+
+/// Records that a node is still around. Returns false if the node isn't
+/// registered, it has to register again.
+pub fn seen(db: &SqliteConnection, name: &str) -> QueryResult<bool> {
+    let updated = diesel::update(judge_node::table.filter(judge_node::name.eq(name)))
+        .set(judge_node::seen_at.eq(Utc::now().naive_utc()))
+        .execute(db)?;
+
+    Ok(updated == 1)
+}
+
+/// Drops nodes that have stopped sending heartbeats, forever.
+pub fn reap(db: &SqliteConnection, dispatcher: &Dispatcher) {
+    loop {
+        thread::sleep(HEARTBEAT_INTERVAL);
+
+        let cutoff = Utc::now().naive_utc() - Duration::seconds(NODE_TIMEOUT);
+        let result = judge_node::table
+            .filter(judge_node::seen_at.lt(cutoff))
+            .select(judge_node::name)
+            .load::<String>(db)
+            .and_then(|names| {
+                for name in names {
+                    dispatcher.release(db, &worker_prefix(&name))?;
+                    diesel::delete(judge_node::table.filter(judge_node::name.eq(&name)))
+                        .execute(db)?;
+
+                    warn!("Judge node {} stopped responding", name);
+                }
+
+                Ok(())
+            });
+
+        if let Err(err) = result {
+            error!("Couldn't check on the judge nodes: {}", err);
+        }
+    }
+}
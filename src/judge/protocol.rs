@@ -0,0 +1,130 @@
+//! The messages judge nodes and the server send each other. Nodes make all
+//! of the requests, as JSON to `/judge/v1/` on the server with the shared
+//! token in the `Authorization` header.
+//!
+//! * `register` - A node starting up sends `Register` and gets `Registered`.
+//! * `heartbeat` - Nodes send `Heartbeat` so the server knows they're still
+//!   around and get `Registered` back. Unknown nodes get a 404 and have to
+//!   register again.
+//! * `work` - A worker sends `Fetch` and gets `Work`.
+//! * `packages/{pid}/{lid}` - Gets a `PackageUpload` with a problem's tests
+//!   when a node doesn't have the version a job needs.
+//! * `judged` - A worker sends `Judged` when it's done with a solution and
+//!   gets `Saved`.
+//! * `ran` - A worker sends `Ran` when it's done running code and gets
+//!   `Saved`.
+//! * `giveback` - A worker sends `GiveBack` when it can't judge a solution
+//!   for reasons of its own, such as not getting its package, and gets
+//!   `Saved`. The solution is handed to the next worker asking for work.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{ Hash, Hasher };
+
+use serde::Serialize;
+use serde_json;
+
+use super::{ Package, Report, Run, SourceFile, Toolchain };
+use super::queue::RunTask;
+
+/// Nodes and servers only talk to each other if they use the same version.
+pub const VERSION: u32 = 3;
+
+/// The path everything is sent to.
+pub const PREFIX: &str = "/judge/v1";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Register {
+    pub node: String,
+    pub version: u32,
+    pub workers: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Registered {
+    /// How often the node has to send a heartbeat in seconds.
+    pub heartbeat: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Heartbeat {
+    pub node: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Fetch {
+    /// The name of the worker, the node's name followed by a `/`.
+    pub worker: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum Work {
+    /// There's nothing to do right now.
+    Idle,
+    Judge {
+        /// The queue entry to report the result to.
+        entry: i32,
+        sid: i32,
+        language: String,
+        /// `None` if the solution's language can't be judged.
+        toolchain: Option<Toolchain>,
+        files: Vec<SourceFile>,
+        package: PackageRef,
+    },
+    Run {
+        id: u64,
+        task: RunTask,
+    },
+}
+
+/// The version of a problem's package a solution is judged with.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PackageRef {
+    pub pid: i32,
+    /// The language the package's limits are for.
+    pub lid: i32,
+    pub digest: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PackageUpload {
+    pub digest: String,
+    pub package: Package,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Judged {
+    pub worker: String,
+    pub entry: i32,
+    pub result: Result<Report, String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Ran {
+    pub worker: String,
+    pub id: u64,
+    pub result: Result<Run, String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GiveBack {
+    pub worker: String,
+    pub entry: i32,
+}
+
+/// Whether the server kept a result. Results are dropped when the work was
+/// given to someone else.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Saved {
+    pub saved: bool,
+}
+
+/// Identifies a version of a package so nodes only download packages that
+/// have changed, or anything else that's sent or judged.
+pub fn digest<T: Serialize>(value: &T) -> String {
+    let mut hasher = DefaultHasher::new();
+    serde_json::to_string(value)
+        .unwrap_or_default()
+        .hash(&mut hasher);
+
+    format!("{:016x}", hasher.finish())
+}
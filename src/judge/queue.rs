@@ -0,0 +1,429 @@
+use std::collections::HashMap;
+use std::sync::{ Arc, Mutex };
+use std::sync::atomic::{ AtomicUsize, Ordering };
+use std::sync::mpsc::{ self, Receiver, SyncSender, TrySendError };
+use std::thread;
+use std::time::Duration;
+
+use chrono::{ NaiveDateTime, Utc };
+use diesel::{ self, prelude::*, sql_types::{ Integer, Nullable, Text } };
+use failure::{ self, Error };
+use futures::{ future::{ self, Either }, Future, sync::oneshot };
+
+use ::db::{
+    self,
+    last_insert_rowid,
+    models::{ QueueEntry, Solution },
+    schema::{ judge_node, queue, rejudge, rejudge_change, solution },
+};
+use super::{ Judge, Job, Limits, Report, Run, Toolchain, nodes, store_error, store_report };
+
+/// How long an idle worker waits before checking the queue again.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Workers in this process are named with this prefix.
+pub const LOCAL_PREFIX: &str = "local-";
+
+/// How many runs can wait for a worker before more are turned away.
+const RUN_BACKLOG: usize = 16;
+
+/// How long a run can wait for a worker on top of the time it's allowed to
+/// compile and run for, before whoever is waiting on it gives up.
+const RUN_MARGIN: Duration = Duration::from_secs(30);
+
+// Compares the start of workers' names.
+sql_function!(fn substr(x: Nullable<Text>, start: Integer, length: Integer) -> Nullable<Text>);
+
+/// Code to run once with the given input instead of being judged.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RunTask {
+    pub toolchain: Toolchain,
+    pub code: String,
+    pub input: String,
+    pub limits: Limits,
+}
+
+impl RunTask {
+    pub fn run(&self, judge: &Judge) -> Result<Run, Error> {
+        judge.execute(&self.toolchain, &self.code, &self.input, &self.limits)
+    }
+}
+
+type Reply = oneshot::Sender<Result<Run, String>>;
+
+struct RunRequest {
+    task: RunTask,
+    reply: Reply,
+}
+
+/// Hands code to the workers to be run once. Someone is waiting on the
+/// result of a run so workers take them before queued solutions.
+#[derive(Clone)]
+pub struct Runner {
+    sender: SyncSender<RunRequest>,
+    /// How many workers this process has, runs are left to judge nodes if
+    /// there aren't any.
+    workers: usize,
+}
+
+impl Runner {
+    pub fn run(&self, db: &SqliteConnection, toolchain: Toolchain, code: String, input: String, limits: Limits) -> Box<Future<Item = Run, Error = Error>> {
+        // Nothing would ever take the run without workers or nodes.
+        if self.workers == 0 {
+            match judge_node::table.count().get_result::<i64>(db) {
+                Ok(0) => return Box::new(future::err(failure::err_msg("The judge isn't running any code."))),
+                Ok(_) => (),
+                Err(err) => return Box::new(future::err(Error::from(err))),
+            }
+        }
+
+        let wait = Duration::from_millis(Limits::compile().wall_time + limits.wall_time) + RUN_MARGIN;
+        let (reply, result) = oneshot::channel();
+        let request = RunRequest {
+            task: RunTask {
+                toolchain: toolchain,
+                code: code,
+                input: input,
+                limits: limits,
+            },
+            reply: reply,
+        };
+
+        let error = match self.sender.try_send(request) {
+            Ok(()) => None,
+            Err(TrySendError::Full(_)) => Some("The judge is busy, try again in a moment."),
+            Err(TrySendError::Disconnected(_)) => Some("The judge isn't running any code."),
+        };
+
+        if let Some(error) = error {
+            return Box::new(future::err(failure::err_msg(error)));
+        }
+
+        // A node can take the run and never send its result back, so the
+        // run is given up on once it's had long enough.
+        let (expire, expired) = oneshot::channel::<()>();
+        let timer = thread::Builder::new()
+            .name("run-timeout".into())
+            .spawn(move || {
+                thread::sleep(wait);
+                let _ = expire.send(());
+            });
+
+        if let Err(err) = timer {
+            return Box::new(future::err(Error::from(err)));
+        }
+
+        let result = result
+            .map_err(|_| failure::err_msg("The judge stopped before running the code."))
+            .and_then(|result| result.map_err(failure::err_msg))
+            .select2(expired)
+            .then(|outcome| match outcome {
+                Ok(Either::A((run, _))) => Ok(run),
+                Err(Either::A((err, _))) => Err(err),
+                Ok(Either::B(_)) | Err(Either::B(_)) => Err(failure::err_msg("The judge didn't run the code in time.")),
+            });
+
+        Box::new(result)
+    }
+}
+
+/// Something for a worker to do.
+pub enum Task {
+    /// A solution claimed from the queue.
+    Judge { entry: i32, job: Job },
+    /// Code sent to the runner, the result is handed back with its id.
+    Run { id: u64, task: RunTask },
+}
+
+/// Hands out work to workers, whether they're in this process or on judge
+/// nodes, and takes their results back.
+#[derive(Clone)]
+pub struct Dispatcher {
+    runs: Arc<Mutex<Receiver<RunRequest>>>,
+    /// Runs that have been handed out by id, with the worker running them.
+    running: Arc<Mutex<HashMap<u64, (String, Reply)>>>,
+    next_run: Arc<AtomicUsize>,
+}
+
+impl Dispatcher {
+    /// Gets the next thing for a worker to do, runs come before queued
+    /// solutions.
+    pub fn next(&self, db: &SqliteConnection, worker: &str) -> QueryResult<Option<Task>> {
+        let request = self.runs.lock()
+            .ok()
+            .and_then(|runs| runs.try_recv().ok());
+
+        if let Some(request) = request {
+            let id = self.next_run.fetch_add(1, Ordering::SeqCst) as u64;
+            if let Ok(mut running) = self.running.lock() {
+                running.insert(id, (worker.into(), request.reply));
+            }
+
+            return Ok(Some(Task::Run { id: id, task: request.task }));
+        }
+
+        let entry = match claim(db, worker)? {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+
+        match Job::load(db, entry.sid) {
+            Ok(job) => Ok(Some(Task::Judge { entry: entry.id, job: job })),
+            Err(err) => {
+                // The database failing, such as when it's locked, doesn't
+                // mean the solution can't be judged so it's given back.
+                // Solutions that can't be loaded otherwise can't be judged
+                // either, the error is saved as their result.
+                let transient = err.downcast_ref::<diesel::result::Error>()
+                    .map(|err| match *err {
+                        diesel::result::Error::NotFound => false,
+                        _ => true,
+                    })
+                    .unwrap_or(false);
+
+                warn!("{} couldn't load solution {}: {}", worker, entry.sid, err);
+                if transient {
+                    unclaim(db, worker, entry.id)?;
+                } else {
+                    complete(db, worker, entry.id, &Err(err.to_string()))?;
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    /// Takes back a solution a worker couldn't judge for reasons of its
+    /// own, see `unclaim`.
+    pub fn give_back(&self, db: &SqliteConnection, worker: &str, entry: i32) -> QueryResult<bool> {
+        unclaim(db, worker, entry)
+    }
+
+    /// Saves the result of judging a solution, see `complete`.
+    pub fn judged(&self, db: &SqliteConnection, worker: &str, entry: i32, result: &Result<Report, String>) -> QueryResult<bool> {
+        complete(db, worker, entry, result)
+    }
+
+    /// Sends the result of a run to whoever is waiting on it. Returns false
+    /// if the run wasn't handed to the worker.
+    pub fn ran(&self, worker: &str, id: u64, result: Result<Run, String>) -> bool {
+        let reply = self.running.lock()
+            .ok()
+            .and_then(|mut running| {
+                let owned = running.get(&id)
+                    .map(|&(ref owner, _)| owner == worker)
+                    .unwrap_or(false);
+
+                if owned { running.remove(&id) } else { None }
+            });
+
+        match reply {
+            Some((_, reply)) => {
+                // Whoever asked for the run may have given up on it already.
+                let _ = reply.send(result);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Takes back everything handed to workers whose names start with the
+    /// prefix. Their solutions are judged by someone else and their runs
+    /// fail.
+    pub fn release(&self, db: &SqliteConnection, prefix: &str) -> QueryResult<()> {
+        // `LIKE` would treat `_` in node names as a wildcard and ignore case.
+        let claimed = substr(queue::worker, 1, prefix.chars().count() as i32).eq(prefix);
+        diesel::update(queue::table.filter(claimed))
+            .set((queue::worker.eq(None::<String>), queue::claimed_at.eq(None::<NaiveDateTime>)))
+            .execute(db)?;
+
+        if let Ok(mut running) = self.running.lock() {
+            running.retain(|_, &mut (ref worker, _)| !worker.starts_with(prefix));
+        }
+
+        Ok(())
+    }
+}
+
+/// Adds a solution to the queue. If the solution is already queued it's
+/// put back at the end of the queue and any claim on it is dropped, so a
+/// worker that's already judging it won't be able to save its result.
+pub fn enqueue(db: &SqliteConnection, sid: i32) -> QueryResult<()> {
+    diesel::replace_into(queue::table)
+        .values(queue::sid.eq(sid))
+        .execute(db)?;
+
+    Ok(())
+}
+
+/// Queues solutions to be judged again. Their current verdicts are kept so
+/// the changes can be shown once they've been judged. Returns the id of
+/// the new rejudge.
+pub fn rejudge(db: &SqliteConnection, description: &str, solutions: &[Solution]) -> QueryResult<i32> {
+    db.transaction(|| {
+        diesel::insert_into(rejudge::table)
+            .values(rejudge::description.eq(description))
+            .execute(db)?;
+
+        let rid = diesel::select(last_insert_rowid).get_result::<i32>(db)?;
+
+        for solution in solutions {
+            diesel::insert_into(rejudge_change::table)
+                .values((
+                    rejudge_change::rid.eq(rid),
+                    rejudge_change::sid.eq(solution.id),
+                    rejudge_change::old_verdict.eq(&solution.verdict),
+                    rejudge_change::old_score.eq(solution.score),
+                ))
+                .execute(db)?;
+
+            enqueue(db, solution.id)?;
+        }
+
+        Ok(rid)
+    })
+}
+
+/// Claims the oldest unclaimed solution in the queue for a worker.
+pub fn claim(db: &SqliteConnection, worker: &str) -> QueryResult<Option<QueueEntry>> {
+    db.transaction(|| {
+        let entry = queue::table
+            .filter(queue::worker.is_null())
+            .order(queue::id.asc())
+            .first::<QueueEntry>(db)
+            .optional()?;
+
+        let entry = match entry {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+
+        // Only take the entry if nobody else got to it first.
+        let claimed = diesel::update(queue::table.filter(queue::id.eq(entry.id)).filter(queue::worker.is_null()))
+            .set((queue::worker.eq(worker), queue::claimed_at.eq(Utc::now().naive_utc())))
+            .execute(db)?;
+
+        if claimed == 1 {
+            Ok(Some(entry))
+        } else {
+            Ok(None)
+        }
+    })
+}
+
+/// Drops a worker's claim on an entry so it's judged again, by whichever
+/// worker gets to it first. Returns false if the worker didn't hold it.
+pub fn unclaim(db: &SqliteConnection, worker: &str, entry: i32) -> QueryResult<bool> {
+    let unclaimed = diesel::update(queue::table.filter(queue::id.eq(entry)).filter(queue::worker.eq(worker)))
+        .set((queue::worker.eq(None::<String>), queue::claimed_at.eq(None::<NaiveDateTime>)))
+        .execute(db)?;
+
+    Ok(unclaimed == 1)
+}
+
+/// Removes a claimed entry from the queue and saves the result of judging
+/// it. Nothing is saved if the worker no longer holds the claim, which
+/// happens when the solution was queued again while it was being judged.
+pub fn complete(db: &SqliteConnection, worker: &str, entry: i32, result: &Result<Report, String>) -> QueryResult<bool> {
+    db.transaction(|| {
+        let sid = queue::table
+            .filter(queue::id.eq(entry))
+            .filter(queue::worker.eq(worker))
+            .select(queue::sid)
+            .first::<i32>(db)
+            .optional()?;
+
+        let sid = match sid {
+            Some(sid) => sid,
+            None => return Ok(false),
+        };
+
+        diesel::delete(queue::table.filter(queue::id.eq(entry)))
+            .execute(db)?;
+
+        match *result {
+            Ok(ref report) => store_report(db, sid, report)?,
+            Err(ref error) => store_error(db, sid, error)?,
+        }
+
+        // Finish any rejudges that were waiting on this solution.
+        let (verdict, score) = solution::table
+            .filter(solution::id.eq(sid))
+            .select((solution::verdict, solution::score))
+            .first::<(String, i32)>(db)?;
+
+        diesel::update(rejudge_change::table
+                .filter(rejudge_change::sid.eq(sid))
+                .filter(rejudge_change::new_verdict.is_null()))
+            .set((rejudge_change::new_verdict.eq(verdict), rejudge_change::new_score.eq(score)))
+            .execute(db)?;
+
+        Ok(true)
+    })
+}
+
+/// Starts the background workers that judge queued solutions and run code
+/// sent to the returned runner. Judge nodes get their work from the
+/// returned dispatcher.
+pub fn start(workers: usize, judge: Judge) -> Result<(Runner, Dispatcher), Error> {
+    let (sender, receiver) = mpsc::sync_channel(RUN_BACKLOG);
+    let dispatcher = Dispatcher {
+        runs: Arc::new(Mutex::new(receiver)),
+        running: Arc::new(Mutex::new(HashMap::new())),
+        next_run: Arc::new(AtomicUsize::new(0)),
+    };
+
+    // Workers from a previous run of the server are gone, so whatever they
+    // were judging needs to be judged again.
+    dispatcher.release(&db::establish()?, LOCAL_PREFIX)?;
+
+    for i in 0..workers {
+        let name = format!("{}{}", LOCAL_PREFIX, i);
+        let conn = db::establish()?;
+        let judge = judge.clone();
+        let dispatcher = dispatcher.clone();
+
+        thread::Builder::new()
+            .name(name.clone())
+            .spawn(move || work(&name, &conn, &judge, &dispatcher))?;
+    }
+
+    let conn = db::establish()?;
+    let reaper = dispatcher.clone();
+    thread::Builder::new()
+        .name("node-reaper".into())
+        .spawn(move || nodes::reap(&conn, &reaper))?;
+
+    Ok((Runner { sender: sender, workers: workers }, dispatcher))
+}
+
+fn work(name: &str, db: &SqliteConnection, judge: &Judge, dispatcher: &Dispatcher) {
+    loop {
+        match dispatcher.next(db, name) {
+            Ok(Some(Task::Run { id, task })) => {
+                let result = task.run(judge).map_err(|err| err.to_string());
+                if let Err(ref err) = result {
+                    warn!("{} couldn't run code: {}", name, err);
+                }
+
+                dispatcher.ran(name, id, result);
+            }
+            Ok(Some(Task::Judge { entry, job })) => {
+                let result = job.run(judge).map_err(|err| err.to_string());
+                if let Err(ref err) = result {
+                    warn!("{} couldn't judge solution {}: {}", name, job.sid, err);
+                }
+
+                match dispatcher.judged(db, name, entry, &result) {
+                    Ok(true) => info!("{} judged solution {}", name, job.sid),
+                    Ok(false) => info!("{} dropped solution {}, it was queued again", name, job.sid),
+                    Err(err) => error!("{} couldn't save solution {}: {}", name, job.sid, err),
+                }
+            }
+            Ok(None) => thread::sleep(POLL_INTERVAL),
+            Err(err) => {
+                error!("{} couldn't read the queue: {}", name, err);
+                thread::sleep(POLL_INTERVAL);
+            }
+        }
+    }
+}
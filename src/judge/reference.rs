@@ -0,0 +1,130 @@
+//! Problems can only be published once their reference solution is accepted
+//! by their tests. The reference solution is judged in the background and
+//! its result is kept with a digest of what it was judged with, so
+//! publishing only has to look it up and any change to the problem gets it
+//! judged again.
+
+use std::thread;
+
+use chrono::{ Duration, Utc };
+use diesel::{ self, prelude::* };
+use failure::Error;
+
+use ::db::{
+    self,
+    models::{ Language, Problem, ReferenceRun },
+    schema::{ language, reference_run },
+};
+use super::{ Judge, Package, SourceFile, Toolchain, Verdict, load_package, protocol };
+
+/// How long judging can take before it's assumed to have been lost, such as
+/// when the server was restarted in the middle of it.
+const STALE_AFTER_MINUTES: i64 = 10;
+
+/// Where a problem's reference solution stands.
+pub enum Reference {
+    Accepted,
+    /// The solution wasn't accepted or can't be judged, with why.
+    Rejected(String),
+    /// The solution is being judged in the background.
+    Judging,
+}
+
+/// Everything the reference solution is judged with.
+#[derive(Serialize)]
+struct Task<'a> {
+    toolchain: &'a Toolchain,
+    files: &'a [SourceFile],
+    package: &'a Package,
+}
+
+/// Gets the result of judging a problem's reference solution as the problem
+/// is now. If it hasn't been judged like this yet, it's started in the
+/// background.
+pub fn check(db: &SqliteConnection, judge: &Judge, problem: &Problem) -> Result<Reference, Error> {
+    let lid = match problem.reference_language.filter(|_| !problem.reference_code.trim().is_empty()) {
+        Some(lid) => lid,
+        None => return Ok(Reference::Rejected("A problem needs a reference solution before it can be published.".into())),
+    };
+
+    let language = match language::table.filter(language::id.eq(lid)).first::<Language>(db).optional()? {
+        Some(language) => language,
+        None => return Ok(Reference::Rejected("The reference solution's language doesn't exist.".into())),
+    };
+
+    let toolchain = match Toolchain::for_language(&language) {
+        Some(toolchain) => toolchain,
+        None => return Ok(Reference::Rejected(format!("The reference solution can't be judged, {} isn't enabled.", language.name))),
+    };
+
+    let package = match load_package(db, problem.id, lid) {
+        Ok(package) => package,
+        Err(err) => return Ok(Reference::Rejected(format!("The problem can't be judged: {}", err))),
+    };
+
+    let files = vec![SourceFile { name: language.source.clone(), content: problem.reference_code.clone() }];
+    let digest = protocol::digest(&Task { toolchain: &toolchain, files: &files, package: &package });
+
+    let run = reference_run::table
+        .find(problem.id)
+        .first::<ReferenceRun>(db)
+        .optional()?;
+
+    let now = Utc::now().naive_utc();
+    match run {
+        Some(ref run) if run.digest == digest && run.done => {
+            return Ok(if run.accepted { Reference::Accepted } else { Reference::Rejected(run.message.clone()) });
+        }
+        Some(ref run) if run.digest == digest && run.started_at > now - Duration::minutes(STALE_AFTER_MINUTES) => {
+            return Ok(Reference::Judging);
+        }
+        _ => (),
+    }
+
+    diesel::replace_into(reference_run::table)
+        .values((
+            reference_run::pid.eq(problem.id),
+            reference_run::digest.eq(&digest),
+            reference_run::started_at.eq(now),
+        ))
+        .execute(db)?;
+
+    let judge = judge.clone();
+    let pid = problem.id;
+    thread::Builder::new()
+        .name(format!("reference-{}", pid))
+        .spawn(move || {
+            let (accepted, message) = match judge.judge(&toolchain, &files, &package) {
+                Ok(ref report) if report.verdict == Verdict::Accepted => (true, String::new()),
+                Ok(report) => (false, format!(
+                    "The reference solution has to be accepted but it got {}, passing {} of {} tests. {}",
+                    report.verdict.name(), report.passed(), package.tests.len(), report.message,
+                )),
+                Err(err) => (false, format!("The judge failed to judge the reference solution: {}", err)),
+            };
+
+            if let Err(err) = finish(pid, &digest, accepted, &message) {
+                error!("Couldn't save the reference run of problem {}: {}", pid, err);
+            }
+        })?;
+
+    Ok(Reference::Judging)
+}
+
+/// Saves the result of judging a reference solution, unless the problem has
+/// been changed and judged again since.
+fn finish(pid: i32, digest: &str, accepted: bool, message: &str) -> Result<(), Error> {
+    let db = db::establish()?;
+
+    diesel::update(reference_run::table
+            .filter(reference_run::pid.eq(pid))
+            .filter(reference_run::digest.eq(digest)))
+        .set((
+            reference_run::done.eq(true),
+            reference_run::accepted.eq(accepted),
+            reference_run::message.eq(message),
+        ))
+        .execute(&db)?;
+
+    Ok(())
+}
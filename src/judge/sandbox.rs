@@ -0,0 +1,725 @@
+use std::ffi::CString;
+use std::fs::{ self, File, OpenOptions };
+use std::io::{ self, Read, Write };
+use std::mem;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{ OpenOptionsExt, symlink };
+use std::os::unix::io::FromRawFd;
+use std::os::unix::process::CommandExt;
+use std::path::{ Path, PathBuf };
+use std::process::{ Command, Stdio };
+use std::thread;
+use std::time::{ Duration, Instant };
+
+use libc;
+use tempdir::TempDir;
+
+/// The only places programs in the sandbox will look for executables.
+const PATH: &str = "/usr/local/bin:/usr/bin:/bin";
+
+/// The host directories programs in the sandbox can see, read only. Nothing
+/// else on the host exists for them, including the server's files and other
+/// sandboxes.
+const SYSTEM_DIRS: &[&str] = &["/bin", "/etc", "/lib", "/lib32", "/lib64", "/sbin", "/usr"];
+
+const DEVICES: &[&str] = &["/dev/null", "/dev/zero", "/dev/random", "/dev/urandom"];
+
+/// Where the sandbox's directory is for programs in it.
+const WORK_DIR: &str = "/sandbox";
+
+/// The empty directory in the judge's root that each sandboxed process
+/// mounts its own root on.
+const JAIL: &str = "jail";
+
+/// The user and group programs run as. They're only mapped to the server's
+/// user from outside, so programs own nothing but the sandbox's files.
+const SANDBOX_ID: u32 = 1000;
+
+/// The host user and group programs run as when the server runs as root,
+/// since root isn't held to the process limit.
+const HOST_ID: u32 = 65534;
+
+/// The most processes and threads a program can have at once, so fork bombs
+/// can't take the machine down.
+const MAX_PROCESSES: u64 = 256;
+
+/// The sizes of the root and `/tmp` file systems, which are kept in memory.
+const ROOT_OPTIONS: &[u8] = b"mode=755,size=1m\0";
+const TMP_OPTIONS: &[u8] = b"mode=1777,size=64m\0";
+
+/// Resource limits applied to a single process in the sandbox.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Limits {
+    /// CPU time in milliseconds.
+    pub time: u64,
+    /// Wall clock time in milliseconds.
+    pub wall_time: u64,
+    /// Address space in kilobytes, `None` if it isn't limited.
+    pub memory: Option<u64>,
+    /// The largest file the process may write in kilobytes.
+    pub output: u64,
+}
+
+impl Limits {
+    /// Limits with the given CPU time in milliseconds and memory and output
+    /// in megabytes. Programs get three times as long in wall clock time so
+    /// they aren't cut short when the machine is busy.
+    pub fn new(time: u64, memory: u64, output: u64) -> Self {
+        Limits {
+            time: time,
+            wall_time: time * 3,
+            memory: Some(memory * 1024),
+            output: output * 1024,
+        }
+    }
+
+    /// Gives programs more time, for languages that are slower than others.
+    pub fn scale_time(self, multiplier: f64) -> Self {
+        Limits {
+            time: (self.time as f64 * multiplier) as u64,
+            wall_time: (self.wall_time as f64 * multiplier) as u64,
+            ..self
+        }
+    }
+
+    /// Compilers need a lot more room than the programs they compile so
+    /// these are much more relaxed than the default limits.
+    pub fn compile() -> Self {
+        Limits {
+            time: 30_000,
+            wall_time: 60_000,
+            memory: None,
+            output: 64 * 1024,
+        }
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits::new(2_000, 256, 64)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Status {
+    Exited(i32),
+    Signaled(i32),
+    TimedOut,
+    /// A WebAssembly module hit a trap, such as dividing by zero or reading
+    /// outside of its memory.
+    Trapped,
+}
+
+/// How a process in the sandbox finished and the resources it used.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Execution {
+    pub status: Status,
+    /// CPU time in milliseconds.
+    pub time: u64,
+    /// Wall clock time in milliseconds.
+    pub wall_time: u64,
+    /// Peak resident memory in kilobytes.
+    pub memory: u64,
+}
+
+impl Execution {
+    pub fn success(&self) -> bool {
+        self.status == Status::Exited(0)
+    }
+
+    pub fn time_exceeded(&self, limits: &Limits) -> bool {
+        match self.status {
+            Status::TimedOut | Status::Signaled(libc::SIGXCPU) => true,
+            _ => self.time > limits.time,
+        }
+    }
+
+    pub fn memory_exceeded(&self, limits: &Limits) -> bool {
+        limits.memory
+            .map(|memory| self.memory > memory)
+            .unwrap_or(false)
+    }
+
+    pub fn describe(&self) -> String {
+        match self.status {
+            Status::Exited(code) => format!("Exited with status {}.", code),
+            Status::Signaled(libc::SIGXCPU) => "Time limit exceeded.".into(),
+            Status::Signaled(libc::SIGXFSZ) => "Output limit exceeded.".into(),
+            Status::Signaled(signal) => format!("Killed by signal {}.", signal),
+            Status::TimedOut => "Timed out.".into(),
+            Status::Trapped => "Trapped.".into(),
+        }
+    }
+}
+
+/// A temporary directory that programs are run in. Every process started in
+/// the sandbox gets its own file system with only the system directories and
+/// the sandbox in it, its own process tree, no network access and resource
+/// limits. The directory is removed when the sandbox is dropped.
+pub struct Sandbox {
+    dir: TempDir,
+    jail: PathBuf,
+}
+
+impl Sandbox {
+    pub fn new(root: &Path) -> io::Result<Self> {
+        let jail = root.join(JAIL);
+        fs::create_dir_all(&jail)?;
+
+        let dir = TempDir::new_in(root, "sandbox")?;
+        give_away(dir.path())?;
+
+        Ok(Sandbox {
+            dir: dir,
+            jail: jail,
+        })
+    }
+
+    /// Creates a new sandbox in `root` with a copy of this one's files, so
+    /// a compiled program can be run more than once without anything one
+    /// run leaves behind being seen by the next.
+    pub fn duplicate(&self, root: &Path) -> io::Result<Self> {
+        let sandbox = Sandbox::new(root)?;
+        copy_dir(self.path(), sandbox.path())?;
+        Ok(sandbox)
+    }
+
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// Writes a file in the sandbox, creating any directories in its name.
+    pub fn write<C: AsRef<[u8]>>(&self, name: &str, contents: C) -> io::Result<()> {
+        let path = self.path().join(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        self.create(name)?.write_all(contents.as_ref())
+    }
+
+    /// Reads at most `limit` bytes of a file in the sandbox.
+    pub fn read(&self, name: &str, limit: u64) -> io::Result<String> {
+        let mut buf = Vec::new();
+        self.open(name)?
+            .take(limit)
+            .read_to_end(&mut buf)?;
+        Ok(String::from_utf8_lossy(&buf).into_owned())
+    }
+
+    /// Creates an empty directory in the sandbox that programs can write
+    /// to, replacing whatever was there before.
+    pub fn create_dir(&self, name: &str) -> io::Result<()> {
+        let path = self.path().join(name);
+        match fs::symlink_metadata(&path) {
+            Ok(ref meta) if meta.is_dir() => fs::remove_dir_all(&path)?,
+            Ok(_) => fs::remove_file(&path)?,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => (),
+            Err(err) => return Err(err),
+        }
+
+        fs::create_dir(&path)?;
+        give_away(&path)
+    }
+
+    /// Opens a file in the sandbox for reading. Programs can replace files
+    /// and folders in the sandbox, so links aren't followed and opening a
+    /// pipe doesn't wait for a writer.
+    pub fn open(&self, name: &str) -> io::Result<File> {
+        let mut dir = self.path().to_path_buf();
+        for part in Path::new(name).parent().iter().flat_map(|parent| parent.components()) {
+            dir.push(part);
+            if fs::symlink_metadata(&dir)?.file_type().is_symlink() {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} is in a link.", name)));
+            }
+        }
+
+        OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NOFOLLOW | libc::O_NONBLOCK)
+            .open(self.path().join(name))
+    }
+
+    /// Creates a file in the sandbox, replacing whatever was there before
+    /// instead of writing through it.
+    pub fn create(&self, name: &str) -> io::Result<File> {
+        let path = self.path().join(name);
+        if let Err(err) = fs::remove_file(&path) {
+            if err.kind() != io::ErrorKind::NotFound {
+                return Err(err);
+            }
+        }
+
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)
+    }
+
+    /// Starts a shell command in the sandbox with the given limits.
+    pub fn spawn(&self, cmd: &str, limits: &Limits, stdin: Stdio, stdout: Stdio, stderr: Stdio) -> io::Result<Process> {
+        let limits = *limits;
+        let jail = Jail::new(&self.jail, self.path())?;
+
+        let mut command = Command::new("/bin/sh");
+        command.arg("-c")
+            .arg(cmd)
+            .current_dir(self.path())
+            .env_clear()
+            .env("PATH", PATH)
+            .env("HOME", WORK_DIR)
+            .stdin(stdin)
+            .stdout(stdout)
+            .stderr(stderr);
+
+        unsafe {
+            command.pre_exec(move || isolate(&jail, &limits));
+        }
+
+        let child = command.spawn()?;
+
+        Ok(Process {
+            pid: child.id() as libc::pid_t,
+            started: Instant::now(),
+            limits: limits,
+        })
+    }
+
+    /// Runs a shell command to completion reading stdin from a file in the
+    /// sandbox and writing stdout and stderr to files in the sandbox.
+    pub fn run(&self, cmd: &str, limits: &Limits, stdin: Option<&str>, stdout: &str, stderr: &str) -> io::Result<Execution> {
+        let stdin = match stdin {
+            Some(name) => Stdio::from(self.open(name)?),
+            None => Stdio::null(),
+        };
+
+        let out = self.create(stdout)?;
+        let err = if stdout == stderr {
+            out.try_clone()?
+        } else {
+            self.create(stderr)?
+        };
+
+        self.spawn(cmd, limits, stdin, Stdio::from(out), Stdio::from(err))?
+            .wait()
+    }
+}
+
+/// A process running in the sandbox.
+pub struct Process {
+    pid: libc::pid_t,
+    started: Instant,
+    limits: Limits,
+}
+
+impl Process {
+    /// Waits for the process to finish, killing it if it goes over the wall
+    /// clock limit.
+    pub fn wait(self) -> io::Result<Execution> {
+        let wall_limit = Duration::from_millis(self.limits.wall_time);
+        let mut timed_out = false;
+        let mut status = 0;
+        let mut usage: libc::rusage = unsafe { mem::zeroed() };
+
+        loop {
+            let ret = unsafe { libc::wait4(self.pid, &mut status, libc::WNOHANG, &mut usage) };
+
+            if ret == self.pid {
+                break;
+            } else if ret < 0 {
+                let err = io::Error::last_os_error();
+                if err.kind() != io::ErrorKind::Interrupted {
+                    return Err(err);
+                }
+            } else if !timed_out && self.started.elapsed() > wall_limit {
+                timed_out = true;
+                self.kill();
+            }
+
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        let wall_time = millis(self.started.elapsed());
+
+        // Make sure nothing the process started is left running.
+        self.kill();
+
+        let status = if timed_out {
+            Status::TimedOut
+        } else if libc::WIFSIGNALED(status) {
+            Status::Signaled(libc::WTERMSIG(status))
+        } else {
+            Status::Exited(libc::WEXITSTATUS(status))
+        };
+
+        let time = usage.ru_utime.tv_sec as u64 * 1000 + usage.ru_utime.tv_usec as u64 / 1000
+            + usage.ru_stime.tv_sec as u64 * 1000 + usage.ru_stime.tv_usec as u64 / 1000;
+
+        Ok(Execution {
+            status: status,
+            time: time,
+            wall_time: wall_time,
+            memory: usage.ru_maxrss as u64,
+        })
+    }
+
+    /// Kills the process and everything in its process group.
+    pub fn kill(&self) {
+        unsafe {
+            libc::killpg(self.pid, libc::SIGKILL);
+        }
+    }
+}
+
+/// Creates a pipe for connecting two processes, returning the read and
+/// write ends. Both ends are closed on exec so the only processes holding
+/// them are the ones they're given to.
+pub fn pipe() -> io::Result<(File, File)> {
+    let mut fds = [0; 2];
+
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    unsafe {
+        Ok((File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])))
+    }
+}
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + duration.subsec_nanos() as u64 / 1_000_000
+}
+
+/// The host user programs run as, if it isn't the server's own.
+fn host_user() -> Option<u32> {
+    if unsafe { libc::geteuid() } == 0 { Some(HOST_ID) } else { None }
+}
+
+/// Hands a directory over to the user programs run as, so they can create
+/// files in it.
+fn give_away(path: &Path) -> io::Result<()> {
+    if let Some(id) = host_user() {
+        let path = CString::new(path.as_os_str().as_bytes())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        check(unsafe { libc::chown(path.as_ptr(), id, id) })?;
+    }
+    Ok(())
+}
+
+/// Copies everything in a directory into another, handing it over to the
+/// user programs run as. Links are copied rather than followed and anything
+/// that isn't a file, directory or link is left out.
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        let file_type = entry.file_type()?;
+
+        if file_type.is_dir() {
+            fs::create_dir(&target)?;
+            give_away(&target)?;
+            copy_dir(&entry.path(), &target)?;
+        } else if file_type.is_symlink() {
+            symlink(fs::read_link(entry.path())?, &target)?;
+        } else if file_type.is_file() {
+            fs::copy(entry.path(), &target)?;
+            give_away(&target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Everything `isolate` needs, prepared before forking since the child
+/// can't allocate.
+struct Jail {
+    /// The directory the new root is mounted on.
+    root: CString,
+    /// Host paths and where they're mounted in the new root.
+    dirs: Vec<(CString, CString)>,
+    devices: Vec<(CString, CString)>,
+    dev: CString,
+    /// The sandbox's directory, where it's mounted in the new root and what
+    /// that is called once the new root is `/`.
+    work: (CString, CString, CString),
+    tmp: CString,
+    proc_dir: CString,
+    /// The host user to switch to before isolating.
+    host_user: Option<u32>,
+    /// Maps the root of the namespace the new root is built in to the host
+    /// user and group.
+    uid_map: CString,
+    gid_map: CString,
+    /// Maps `SANDBOX_ID` to the root of that namespace.
+    sandbox_map: CString,
+}
+
+impl Jail {
+    fn new(root: &Path, work: &Path) -> io::Result<Self> {
+        let cstr = |bytes: &[u8]| CString::new(bytes)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err));
+        let path = |path: &Path| cstr(path.as_os_str().as_bytes());
+        let inside = |name: &str| path(&root.join(name.trim_start_matches('/')));
+
+        let mounts = |names: &[&str]| names.iter()
+            .filter(|name| Path::new(name).exists())
+            .map(|name| Ok((cstr(name.as_bytes())?, inside(name)?)))
+            .collect::<io::Result<Vec<_>>>();
+
+        let host_user = host_user();
+        let (uid, gid) = match host_user {
+            Some(id) => (id, id),
+            None => unsafe { (libc::geteuid(), libc::getegid()) },
+        };
+
+        Ok(Jail {
+            root: path(root)?,
+            dirs: mounts(SYSTEM_DIRS)?,
+            devices: mounts(DEVICES)?,
+            dev: inside("/dev")?,
+            work: (path(work)?, inside(WORK_DIR)?, cstr(WORK_DIR.as_bytes())?),
+            tmp: inside("/tmp")?,
+            proc_dir: inside("/proc")?,
+            host_user: host_user,
+            uid_map: cstr(format!("0 {} 1", uid).as_bytes())?,
+            gid_map: cstr(format!("0 {} 1", gid).as_bytes())?,
+            sandbox_map: cstr(format!("{} 0 1", SANDBOX_ID).as_bytes())?,
+        })
+    }
+}
+
+fn check(ret: libc::c_int) -> io::Result<()> {
+    if ret < 0 { Err(io::Error::last_os_error()) } else { Ok(()) }
+}
+
+fn c(bytes: &'static [u8]) -> *const libc::c_char {
+    bytes.as_ptr() as *const libc::c_char
+}
+
+/// Runs in the child between `fork` and `exec` so only async-signal-safe
+/// calls can be made here.
+///
+/// The child unshares its namespaces and forks the first process of a new
+/// process tree, which builds the new root and forks the program. Every
+/// process left in the tree is killed when that first process exits, so
+/// programs can't leave anything running by starting a new session. Both
+/// forks wait for their child and finish the same way it did, so the server
+/// sees how the program finished and the resources it used.
+fn isolate(jail: &Jail, limits: &Limits) -> io::Result<()> {
+    let set = |resource, soft: u64, hard: u64| {
+        let limit = libc::rlimit {
+            rlim_cur: soft as libc::rlim_t,
+            rlim_max: hard as libc::rlim_t,
+        };
+        check(unsafe { libc::setrlimit(resource, &limit) })
+    };
+
+    // A new session puts the process in its own group so that everything it
+    // starts can be killed together.
+    check(unsafe { libc::setsid() })?;
+    set(libc::RLIMIT_CORE, 0, 0)?;
+
+    if let Some(id) = jail.host_user {
+        check(unsafe { libc::setgroups(0, ::std::ptr::null()) })?;
+        check(unsafe { libc::setresgid(id, id, id) })?;
+        check(unsafe { libc::setresuid(id, id, id) })?;
+
+        // Changing users makes `/proc/self` belong to root, which would stop
+        // the process from writing its own user maps.
+        check(unsafe { libc::prctl(libc::PR_SET_DUMPABLE, 1, 0, 0, 0) })?;
+    }
+
+    // The new network namespace leaves programs without any network
+    // interfaces, including loopback.
+    check(unsafe {
+        libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNS | libc::CLONE_NEWNET | libc::CLONE_NEWPID | libc::CLONE_NEWIPC)
+    })?;
+
+    write_file(libc::AT_FDCWD, b"/proc/self/setgroups\0", b"deny")?;
+    write_file(libc::AT_FDCWD, b"/proc/self/uid_map\0", jail.uid_map.as_bytes())?;
+    write_file(libc::AT_FDCWD, b"/proc/self/gid_map\0", jail.gid_map.as_bytes())?;
+
+    // The program's status is passed back through a pipe, since the first
+    // process of a process tree can't be killed by a signal of its own.
+    let mut status = [0; 2];
+    check(unsafe { libc::pipe2(status.as_mut_ptr(), libc::O_CLOEXEC) })?;
+
+    match unsafe { libc::fork() } {
+        -1 => return Err(io::Error::last_os_error()),
+        0 => unsafe { libc::close(status[0]); },
+        pid => relay(pid, Some(status[0]), None),
+    }
+
+    // The host's `/proc` is kept open to set up the program's user namespace
+    // once the rest of the host's file system is gone.
+    let proc_fd = unsafe { libc::open(c(b"/proc\0"), libc::O_PATH | libc::O_DIRECTORY | libc::O_CLOEXEC) };
+    check(proc_fd)?;
+    build_root(jail)?;
+
+    match unsafe { libc::fork() } {
+        -1 => return Err(io::Error::last_os_error()),
+        0 => unsafe { libc::close(status[1]); },
+        pid => relay(pid, None, Some(status[1])),
+    }
+
+    // A second user namespace runs the program as an unprivileged user, so
+    // it can't undo any of the mounts.
+    check(unsafe { libc::unshare(libc::CLONE_NEWUSER) })?;
+    write_file(proc_fd, b"self/setgroups\0", b"deny")?;
+    write_file(proc_fd, b"self/uid_map\0", jail.sandbox_map.as_bytes())?;
+    write_file(proc_fd, b"self/gid_map\0", jail.sandbox_map.as_bytes())?;
+    check(unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) })?;
+
+    // The rlimits are only a backstop, they are set above the real limits so
+    // that going over is seen in the measured usage. The process gets a
+    // SIGXCPU at the soft limit and is killed a second later.
+    let cpu = (limits.time + 999) / 1000 + 1;
+    set(libc::RLIMIT_CPU, cpu, cpu + 1)?;
+
+    // Runtimes reserve a lot more address space than they actually use.
+    if let Some(memory) = limits.memory {
+        set(libc::RLIMIT_AS, memory * 2048, memory * 2048)?;
+    }
+
+    set(libc::RLIMIT_FSIZE, limits.output * 1024, limits.output * 1024)?;
+    set(libc::RLIMIT_NPROC, MAX_PROCESSES, MAX_PROCESSES)?;
+
+    Ok(())
+}
+
+/// Mounts a new root with only the system directories, a few devices, an
+/// empty `/tmp` and the sandbox in it, then switches to it.
+fn build_root(jail: &Jail) -> io::Result<()> {
+    let none = ::std::ptr::null::<libc::c_char>();
+    let no_data = ::std::ptr::null::<libc::c_void>();
+    let tmpfs = c(b"tmpfs\0");
+
+    unsafe {
+        // Nothing mounted from here on is seen by the host.
+        check(libc::mount(none, c(b"/\0"), none, libc::MS_REC | libc::MS_PRIVATE, no_data))?;
+        check(libc::mount(tmpfs, jail.root.as_ptr(), tmpfs, libc::MS_NOSUID | libc::MS_NODEV, ROOT_OPTIONS.as_ptr() as *const _))?;
+
+        for &(ref source, ref target) in &jail.dirs {
+            check(libc::mkdir(target.as_ptr(), 0o755))?;
+            check(libc::mount(source.as_ptr(), target.as_ptr(), none, libc::MS_BIND | libc::MS_REC, no_data))?;
+
+            // Making the directory read only fails if the host mounted it
+            // with flags that can't be changed, its files still belong to
+            // other users though.
+            libc::mount(none, target.as_ptr(), none, libc::MS_BIND | libc::MS_REMOUNT | libc::MS_RDONLY | libc::MS_NOSUID, no_data);
+        }
+
+        check(libc::mkdir(jail.dev.as_ptr(), 0o755))?;
+        for &(ref source, ref target) in &jail.devices {
+            let fd = libc::open(target.as_ptr(), libc::O_CREAT | libc::O_WRONLY | libc::O_CLOEXEC, 0o644);
+            check(fd)?;
+            libc::close(fd);
+            check(libc::mount(source.as_ptr(), target.as_ptr(), none, libc::MS_BIND, no_data))?;
+        }
+
+        check(libc::mkdir(jail.work.1.as_ptr(), 0o755))?;
+        check(libc::mount(jail.work.0.as_ptr(), jail.work.1.as_ptr(), none, libc::MS_BIND, no_data))?;
+
+        check(libc::mkdir(jail.tmp.as_ptr(), 0o1777))?;
+        check(libc::mount(tmpfs, jail.tmp.as_ptr(), tmpfs, libc::MS_NOSUID | libc::MS_NODEV, TMP_OPTIONS.as_ptr() as *const _))?;
+
+        // Some runtimes read `/proc/self`, but not every host lets a user
+        // namespace mount it.
+        check(libc::mkdir(jail.proc_dir.as_ptr(), 0o555))?;
+        let proc_type = c(b"proc\0");
+        libc::mount(proc_type, jail.proc_dir.as_ptr(), proc_type, libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC, no_data);
+
+        // The old root is stacked under the new one by `pivot_root` and then
+        // detached, leaving no way back to the host's files.
+        check(libc::chdir(jail.root.as_ptr()))?;
+        if libc::syscall(libc::SYS_pivot_root, c(b".\0"), c(b".\0")) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        check(libc::umount2(c(b".\0"), libc::MNT_DETACH))?;
+        check(libc::mount(none, c(b"/\0"), none, libc::MS_REMOUNT | libc::MS_BIND | libc::MS_RDONLY | libc::MS_NOSUID | libc::MS_NODEV, no_data))?;
+        check(libc::chdir(jail.work.2.as_ptr()))?;
+    }
+
+    Ok(())
+}
+
+/// Writes a whole file relative to a directory, `path` has to end with a nul.
+fn write_file(dir: libc::c_int, path: &'static [u8], contents: &[u8]) -> io::Result<()> {
+    unsafe {
+        let fd = libc::openat(dir, c(path), libc::O_WRONLY | libc::O_CLOEXEC);
+        check(fd)?;
+        let written = libc::write(fd, contents.as_ptr() as *const _, contents.len());
+        libc::close(fd);
+
+        if written < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Waits for a child and finishes the same way it did. The status is read
+/// from `status_in` when the child passed one on, and written to
+/// `status_out` instead of being raised when this is the first process of a
+/// process tree. Every other descriptor is closed so that pipes given to
+/// the program only stay open as long as it does.
+fn relay(pid: libc::pid_t, status_in: Option<libc::c_int>, status_out: Option<libc::c_int>) -> ! {
+    unsafe {
+        let keep = status_in.or(status_out).unwrap_or(-1);
+        close_all(keep);
+
+        let mut status = 0;
+        while libc::waitpid(pid, &mut status, 0) < 0 {
+            if *libc::__errno_location() != libc::EINTR {
+                libc::_exit(127);
+            }
+        }
+
+        if let Some(fd) = status_in {
+            let mut passed = 0;
+            let size = mem::size_of::<libc::c_int>();
+            if libc::read(fd, &mut passed as *mut libc::c_int as *mut _, size) == size as isize {
+                status = passed;
+            }
+        }
+
+        if let Some(fd) = status_out {
+            libc::write(fd, &status as *const libc::c_int as *const _, mem::size_of::<libc::c_int>());
+            libc::_exit(0);
+        }
+
+        if libc::WIFSIGNALED(status) {
+            let signal = libc::WTERMSIG(status);
+            let mut set: libc::sigset_t = mem::zeroed();
+            libc::sigemptyset(&mut set);
+            libc::sigaddset(&mut set, signal);
+            libc::signal(signal, libc::SIG_DFL);
+            libc::sigprocmask(libc::SIG_UNBLOCK, &set, ::std::ptr::null_mut());
+            libc::kill(libc::getpid(), signal);
+            libc::_exit(128 + signal);
+        }
+
+        libc::_exit(libc::WEXITSTATUS(status))
+    }
+}
+
+/// Closes every descriptor but one.
+unsafe fn close_all(keep: libc::c_int) {
+    // close_range(2) is only in Linux 5.9 and later.
+    const SYS_CLOSE_RANGE: libc::c_long = 436;
+
+    let ranges = [(0, keep - 1), (keep + 1, libc::c_int::max_value())];
+    for &(first, last) in &ranges {
+        if first > last {
+            continue;
+        }
+
+        if libc::syscall(SYS_CLOSE_RANGE, first as libc::c_uint, last as libc::c_uint, 0) < 0 {
+            let mut limit: libc::rlimit = mem::zeroed();
+            libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit);
+            let end = (limit.rlim_cur.min(1 << 20) as libc::c_int).min(last.saturating_add(1));
+            for fd in first..end {
+                libc::close(fd);
+            }
+        }
+    }
+}
@@ -0,0 +1,234 @@
+//! Runs solutions compiled to WASI modules inside of an embedded WebAssembly
+//! runtime instead of as processes. Modules can only use their stdin, stdout
+//! and stderr, they can't see the sandbox's files or the network. Time is
+//! counted in fuel, which the runtime uses up for every instruction, so a
+//! solution takes the same time no matter how busy the machine is. Time
+//! spent in WASI calls doesn't use fuel, so modules are also stopped once
+//! they go over the wall clock limit.
+//!
+//! This needs the server and judge nodes to be built with the `wasm` feature.
+
+use failure::Error;
+
+use super::sandbox::{ Execution, Limits, Sandbox };
+
+/// How much fuel a module gets for every millisecond of its time limit.
+#[cfg(feature = "wasm")]
+const FUEL_PER_MS: u64 = 1_000_000;
+
+#[cfg(not(feature = "wasm"))]
+pub fn run(_sandbox: &Sandbox, _cmd: &str, _limits: &Limits, _stdin: Option<&str>, _stdout: &str, _stderr: &str) -> Result<Execution, Error> {
+    Err(::failure::err_msg("The judge was built without WebAssembly support."))
+}
+
+/// Runs a module in the sandbox with the given files as its stdio. The
+/// command is the module's file name followed by its arguments.
+#[cfg(feature = "wasm")]
+pub fn run(sandbox: &Sandbox, cmd: &str, limits: &Limits, stdin: Option<&str>, stdout: &str, stderr: &str) -> Result<Execution, Error> {
+    use std::fmt::Display;
+    use std::io::Read;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::{ Duration, Instant };
+
+    use failure;
+    use libc;
+    use wasi_common::{ I32Exit, pipe::{ ReadPipe, WritePipe } };
+    use wasmtime::{ self, Config, Engine, Linker, Module, Store, Trap };
+    use wasmtime_wasi::{ self, WasiCtx, WasiCtxBuilder };
+
+    use super::sandbox::Status;
+
+    struct State {
+        wasi: WasiCtx,
+        memory: MemoryLimiter,
+    }
+
+    fn runtime<E: Display>(err: E) -> Error {
+        failure::err_msg(format!("The WebAssembly runtime failed: {}", err))
+    }
+
+    let module = cmd.split_whitespace()
+        .next()
+        .ok_or_else(|| failure::err_msg("The run command doesn't name a module."))?;
+
+    let read = |name: &str| -> Result<Vec<u8>, Error> {
+        let mut buf = Vec::new();
+        sandbox.open(name)?.read_to_end(&mut buf)?;
+        Ok(buf)
+    };
+
+    let input = match stdin {
+        Some(name) => read(name)?,
+        None => Vec::new(),
+    };
+
+    let mut config = Config::new();
+    config.consume_fuel(true);
+    config.epoch_interruption(true);
+    let engine = Engine::new(&config).map_err(runtime)?;
+
+    // The compile command is expected to leave a module behind, so one that
+    // can't be loaded is the language's fault rather than the solution's.
+    let module = read(module)
+        .and_then(|bytes| Module::new(&engine, &bytes).map_err(|err| failure::err_msg(err.to_string())))
+        .map_err(|err| failure::err_msg(format!("The module couldn't be loaded: {}", err)))?;
+
+    let output_limit = limits.output as usize * 1024;
+    let captured_out = WritePipe::new(LimitedWriter::new(output_limit));
+    let captured_err = WritePipe::new(LimitedWriter::new(output_limit));
+
+    let mut builder = WasiCtxBuilder::new()
+        .stdin(Box::new(ReadPipe::from(input)))
+        .stdout(Box::new(captured_out.clone()))
+        .stderr(Box::new(captured_err.clone()));
+
+    for arg in cmd.split_whitespace() {
+        builder = builder.arg(arg).map_err(runtime)?;
+    }
+
+    let mut linker = Linker::new(&engine);
+    wasmtime_wasi::add_to_linker(&mut linker, |state: &mut State| &mut state.wasi).map_err(runtime)?;
+
+    let mut store = Store::new(&engine, State {
+        wasi: builder.build(),
+        memory: MemoryLimiter {
+            cap: limits.memory.map(|memory| memory as usize * 1024),
+            peak: 0,
+        },
+    });
+    store.limiter(|state| &mut state.memory);
+    store.add_fuel(limits.time * FUEL_PER_MS).map_err(runtime)?;
+
+    // The module traps once the epoch is moved past its deadline, which the
+    // timer does when the wall clock limit is up. Dropping `done` stops the
+    // timer early.
+    store.set_epoch_deadline(1);
+    let (done, finished) = mpsc::channel::<()>();
+    let timer = {
+        let engine = engine.clone();
+        let wall_limit = Duration::from_millis(limits.wall_time);
+        thread::spawn(move || {
+            if let Err(mpsc::RecvTimeoutError::Timeout) = finished.recv_timeout(wall_limit) {
+                engine.increment_epoch();
+            }
+        })
+    };
+
+    let started = Instant::now();
+    let result = linker.instantiate(&mut store, &module)
+        .and_then(|instance| instance.get_func(&mut store, "_start")
+            .ok_or_else(|| wasmtime::Error::msg("The module doesn't have a _start function.")))
+        .and_then(|start| start.call(&mut store, &[], &mut []));
+    let wall_time = started.elapsed();
+
+    drop(done);
+    let _ = timer.join();
+
+    let mut status = match result {
+        Ok(()) => Status::Exited(0),
+        Err(ref err) => match err.downcast_ref::<I32Exit>() {
+            Some(exit) => Status::Exited(exit.0),
+            None if err.downcast_ref::<Trap>() == Some(&Trap::OutOfFuel) => Status::TimedOut,
+            None if err.downcast_ref::<Trap>() == Some(&Trap::Interrupt) => Status::TimedOut,
+            None => Status::Trapped,
+        },
+    };
+
+    let fuel = store.fuel_consumed().unwrap_or(0);
+    let memory = store.data().memory.peak / 1024;
+    drop(store);
+
+    let output = captured_out.try_into_inner()
+        .map_err(|_| failure::err_msg("The module's stdout is still in use."))?;
+    let error = captured_err.try_into_inner()
+        .map_err(|_| failure::err_msg("The module's stderr is still in use."))?;
+
+    // Like a process going over its file size limit.
+    if output.exceeded || error.exceeded {
+        status = Status::Signaled(libc::SIGXFSZ);
+    }
+
+    let output = output.data;
+    let mut error = error.data;
+
+    if let (Status::Trapped, Err(err)) = (status, result) {
+        error.extend(format!("\n{}\n", err).bytes());
+    }
+
+    sandbox.write(stdout, &output)?;
+    sandbox.write(stderr, &error)?;
+
+    Ok(Execution {
+        status: status,
+        time: fuel / FUEL_PER_MS,
+        wall_time: wall_time.as_secs() * 1000 + wall_time.subsec_millis() as u64,
+        memory: memory as u64,
+    })
+}
+
+/// Keeps what a module writes to stdout or stderr, up to a limit. Writes
+/// past the limit fail like they would for a process going over its file
+/// size limit.
+#[cfg(feature = "wasm")]
+struct LimitedWriter {
+    data: Vec<u8>,
+    /// In bytes.
+    limit: usize,
+    exceeded: bool,
+}
+
+#[cfg(feature = "wasm")]
+impl LimitedWriter {
+    fn new(limit: usize) -> Self {
+        LimitedWriter {
+            data: Vec::new(),
+            limit: limit,
+            exceeded: false,
+        }
+    }
+}
+
+#[cfg(feature = "wasm")]
+impl ::std::io::Write for LimitedWriter {
+    fn write(&mut self, buf: &[u8]) -> ::std::io::Result<usize> {
+        let room = self.limit - self.data.len();
+        if buf.len() > room {
+            self.exceeded = true;
+            if room == 0 {
+                return Err(::std::io::Error::new(::std::io::ErrorKind::Other, "The output limit was exceeded."));
+            }
+        }
+
+        let written = buf.len().min(room);
+        self.data.extend_from_slice(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> ::std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Stops a module's memory from growing past the memory limit and keeps
+/// track of the most it asked for, so going over the limit is reported even
+/// though the module never got the memory.
+#[cfg(feature = "wasm")]
+struct MemoryLimiter {
+    /// In bytes.
+    cap: Option<usize>,
+    /// In bytes.
+    peak: usize,
+}
+
+#[cfg(feature = "wasm")]
+impl ::wasmtime::ResourceLimiter for MemoryLimiter {
+    fn memory_growing(&mut self, _current: usize, desired: usize, _maximum: Option<usize>) -> bool {
+        self.peak = self.peak.max(desired);
+        self.cap.map(|cap| desired <= cap).unwrap_or(true)
+    }
+
+    fn table_growing(&mut self, _current: u32, _desired: u32, _maximum: Option<u32>) -> bool {
+        true
+    }
+}
@@ -0,0 +1,523 @@
+//! Problems are exported and imported as packages in the directory layout
+//! used by Kattis and the ICPC, so they can be written and kept in git
+//! instead of being typed into the admin pages. A package looks like:
+//!
+//! ```text
+//! problem.yaml                      name, keywords, limits and validation
+//! problem_statement/problem.en.md   the statement in Markdown
+//! data/sample/*.in, *.ans           the sample tests
+//! data/secret/*.in, *.ans           the other tests, in folders or not
+//! output_validators/*/              a custom checker or interactor
+//! submissions/accepted/             the reference solution
+//! ```
+//!
+//! Only the parts of the format the judge can use are read, anything else in
+//! a package is left out.
+
+use std::collections::BTreeMap;
+
+use diesel::prelude::*;
+use failure::Error;
+use serde_yaml::{ self, Value };
+
+use ::{
+    db::{
+        models::{ Language, Problem, TestCase },
+        schema::{ language, test_case },
+    },
+    judge::{ Convention, SourceFile },
+    tags,
+};
+
+const CONFIG: &str = "problem.yaml";
+
+/// Where statements are looked for, the newer version of the format keeps
+/// them in `statement`.
+const STATEMENTS: &[&str] = &[
+    "problem_statement/problem.en.md",
+    "problem_statement/problem.md",
+    "statement/problem.en.md",
+    "statement/problem.md",
+];
+
+/// Statements written in LaTeX are imported as they are.
+const LATEX_STATEMENTS: &[&str] = &[
+    "problem_statement/problem.en.tex",
+    "problem_statement/problem.tex",
+];
+
+const VALIDATORS: &[&str] = &["output_validators/", "output_validator/"];
+const ACCEPTED: &str = "submissions/accepted/";
+
+/// The limits of problems that don't give their own, the same as a new
+/// problem's in the admin form.
+const TIME_LIMIT: i32 = 2000;
+const MEMORY_LIMIT: i32 = 256;
+const OUTPUT_LIMIT: i32 = 64;
+const TOLERANCE: f64 = 1e-6;
+
+/// The parts of `problem.yaml` that are exported.
+#[derive(Serialize)]
+struct Config<'a> {
+    name: &'a str,
+    keywords: Vec<String>,
+    limits: ConfigLimits,
+    validation: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    validator_flags: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ConfigLimits {
+    /// In seconds.
+    time_limit: f64,
+    /// In megabytes.
+    memory: i32,
+    output: i32,
+}
+
+/// A problem read from a package.
+pub struct Package {
+    pub name: String,
+    pub description: String,
+    pub tags: Vec<String>,
+    /// Milliseconds of CPU time each test can use.
+    pub time_limit: i32,
+    /// Megabytes of memory and output each test can use.
+    pub memory_limit: i32,
+    pub output_limit: i32,
+    pub checker: String,
+    pub tolerance: f64,
+    pub interactive: bool,
+    /// The language and code of the custom checker, or of the interactor for
+    /// interactive problems. Either follows Kattis' convention.
+    pub validator: Option<(i32, String)>,
+    pub reference: Option<(i32, String)>,
+    /// The input and output of each test and whether it's a sample.
+    pub tests: Vec<(String, String, bool)>,
+    /// The parts of the package that couldn't be imported as they were.
+    pub notes: Vec<String>,
+}
+
+/// The folder a problem is exported in, its name in lowercase with anything
+/// but letters and numbers turned into dashes.
+pub fn short_name(name: &str) -> String {
+    let name = name.to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect::<String>();
+
+    let name = name.split('-')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+
+    if name.is_empty() { "problem".into() } else { name }
+}
+
+/// Checks that other systems could judge a problem the same way. Checkers
+/// and interactors written for this judge's exit codes don't follow Kattis'
+/// convention.
+pub fn exportable(problem: &Problem) -> Result<(), String> {
+    if problem.interactive && Convention::from_mode(&problem.interactor_convention) != Convention::Kattis {
+        Err("The interactor has to follow Kattis' convention to export the problem.".into())
+    } else if !problem.interactive && problem.checker == "custom" {
+        Err("The custom checker has to be a Kattis output validator to export the problem.".into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Writes a problem out as the files of a package. Programs are named after
+/// their language's source file.
+pub fn export(db: &SqliteConnection, problem: &Problem) -> Result<Vec<SourceFile>, Error> {
+    let languages = language::table.load::<Language>(db)?;
+    let source = |lid: Option<i32>| lid
+        .and_then(|lid| languages.iter().find(|lang| lang.id == lid))
+        .map(|lang| lang.source.clone());
+
+    let tests = test_case::table
+        .filter(test_case::pid.eq(problem.id))
+        .order(test_case::position.asc())
+        .load::<TestCase>(db)?;
+
+    let mut files = Vec::new();
+
+    let validation = if problem.interactive {
+        if let Some(name) = source(problem.interactor_language) {
+            files.push(SourceFile {
+                name: format!("{}interactor/{}", VALIDATORS[0], name),
+                content: problem.interactor_code.clone(),
+            });
+        }
+        "custom interactive"
+    } else if problem.checker == "kattis" {
+        if let Some(name) = source(problem.checker_language) {
+            files.push(SourceFile {
+                name: format!("{}checker/{}", VALIDATORS[0], name),
+                content: problem.checker_code.clone(),
+            });
+        }
+        "custom"
+    } else {
+        "default"
+    };
+
+    // The default validator ignores case and whitespace unless it's told
+    // not to.
+    let validator_flags = match &problem.checker[..] {
+        "exact" if !problem.interactive => Some("case_sensitive space_change_sensitive".into()),
+        "tokens" if !problem.interactive => Some("case_sensitive".into()),
+        "float" if !problem.interactive => Some(format!("float_tolerance {}", problem.tolerance)),
+        _ => None,
+    };
+
+    let config = Config {
+        name: &problem.name,
+        keywords: tags::for_problem(db, problem.id)?,
+        limits: ConfigLimits {
+            time_limit: problem.time_limit as f64 / 1000.0,
+            memory: problem.memory_limit,
+            output: problem.output_limit,
+        },
+        validation: validation,
+        validator_flags: validator_flags,
+    };
+
+    files.push(SourceFile {
+        name: CONFIG.into(),
+        content: serde_yaml::to_string(&config)?,
+    });
+
+    files.push(SourceFile {
+        name: STATEMENTS[0].into(),
+        content: problem.description.clone(),
+    });
+
+    let (mut samples, mut secret) = (0, 0);
+    for test in tests {
+        let (folder, number) = if test.sample {
+            samples += 1;
+            ("sample", samples)
+        } else {
+            secret += 1;
+            ("secret", secret)
+        };
+
+        files.push(SourceFile {
+            name: format!("data/{}/{:03}.in", folder, number),
+            content: test.input,
+        });
+        files.push(SourceFile {
+            name: format!("data/{}/{:03}.ans", folder, number),
+            content: test.output,
+        });
+    }
+
+    if let Some(name) = source(problem.reference_language) {
+        files.push(SourceFile {
+            name: format!("{}{}", ACCEPTED, name),
+            content: problem.reference_code.clone(),
+        });
+    }
+
+    Ok(files)
+}
+
+/// Reads a problem from the files of a package. Programs are matched to the
+/// enabled language with the same source file extension.
+pub fn import(files: &[SourceFile], languages: &[Language]) -> Result<Package, String> {
+    let file = |name: &str| files.iter().find(|file| file.name == name);
+    let mut notes = Vec::new();
+
+    let config = match file(CONFIG) {
+        Some(file) if file.content.trim().is_empty() => Value::Null,
+        Some(file) => serde_yaml::from_str::<Value>(&file.content)
+            .map_err(|err| format!("{} isn't valid YAML: {}", CONFIG, err))?,
+        None => return Err(format!("The package doesn't have a {}.", CONFIG)),
+    };
+
+    let markdown = STATEMENTS.iter().filter_map(|name| file(name)).next();
+    let latex = LATEX_STATEMENTS.iter().filter_map(|name| file(name)).next();
+
+    let description = match (markdown, latex) {
+        (Some(statement), _) => normalize(&statement.content),
+        (None, Some(statement)) => {
+            notes.push(format!("{} is written in LaTeX, it was imported as it is and has to be rewritten in Markdown.", statement.name));
+            normalize(&statement.content)
+        }
+        (None, None) => return Err(format!("The package doesn't have a statement, such as {}.", STATEMENTS[0])),
+    };
+
+    // Older packages only name the problem in the LaTeX statement.
+    let name = text(&config["name"])
+        .or_else(|| latex.and_then(|statement| problem_name(&statement.content)))
+        .ok_or_else(|| format!("{} doesn't name the problem.", CONFIG))?;
+
+    // Keywords are a list in the newer version of the format and separated
+    // by spaces in the older one.
+    let keywords = match config["keywords"] {
+        Value::Sequence(ref keywords) => keywords.iter()
+            .filter_map(|keyword| keyword.as_str())
+            .collect::<Vec<_>>()
+            .join(","),
+        Value::String(ref keywords) => keywords.split_whitespace()
+            .collect::<Vec<_>>()
+            .join(","),
+        _ => String::new(),
+    };
+
+    let tags = tags::parse(&keywords).unwrap_or_else(|error| {
+        notes.push(format!("The keywords weren't imported as tags. {}", error));
+        Vec::new()
+    });
+
+    let limits = &config["limits"];
+    let time_limit = limits["time_limit"].as_f64()
+        .filter(|&seconds| seconds > 0.0)
+        .map(|seconds| (seconds * 1000.0).round() as i32)
+        .unwrap_or_else(|| {
+            notes.push(format!("{} doesn't give a time limit, it was set to {} ms.", CONFIG, TIME_LIMIT));
+            TIME_LIMIT
+        });
+    let megabytes = |value: &Value, default: i32| value.as_i64()
+        .filter(|&megabytes| megabytes > 0)
+        .map(|megabytes| megabytes as i32)
+        .unwrap_or(default);
+    let memory_limit = megabytes(&limits["memory"], MEMORY_LIMIT);
+    let output_limit = megabytes(&limits["output"], OUTPUT_LIMIT);
+
+    let words = |value: &Value| value.as_str()
+        .map(|value| value.split_whitespace().map(String::from).collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    let validation = words(&config["validation"]);
+    let custom = validation.iter().any(|word| word == "custom");
+    let interactive = validation.iter().chain(&words(&config["type"])).any(|word| word == "interactive");
+
+    let flags = words(&config["validator_flags"]);
+    let tolerance = flags.windows(2)
+        .find(|pair| pair[0].starts_with("float_") && pair[0].ends_with("tolerance"))
+        .and_then(|pair| pair[1].parse::<f64>().ok());
+
+    let (checker, tolerance) = if custom && !interactive {
+        ("kattis", TOLERANCE)
+    } else if let Some(tolerance) = tolerance {
+        ("float", tolerance)
+    } else if flags.iter().any(|flag| flag == "space_change_sensitive") {
+        ("exact", TOLERANCE)
+    } else if flags.iter().any(|flag| flag == "case_sensitive") {
+        ("tokens", TOLERANCE)
+    } else {
+        ("case", TOLERANCE)
+    };
+
+    let validator = if custom || interactive {
+        let role = if interactive { "interactor" } else { "checker" };
+        let (file, lid) = VALIDATORS.iter()
+            .filter_map(|folder| program(files, folder, languages))
+            .next()
+            .ok_or_else(|| format!("The package needs an output validator in one of the enabled languages to use as the {}.", role))?;
+
+        // Problems only have room for a single file, so validators that
+        // include headers such as testlib.h can't be imported.
+        let folder = &file.name[..file.name.rfind('/').map(|i| i + 1).unwrap_or(0)];
+        if files.iter().any(|other| other.name.starts_with(folder) && other.name != file.name) {
+            return Err(format!("{} has more than one file, only validators made of a single source file can be imported.", folder));
+        }
+
+        Some((lid, normalize(&file.content)))
+    } else {
+        None
+    };
+
+    let reference = program(files, ACCEPTED, languages)
+        .map(|(file, lid)| (lid, normalize(&file.content)));
+    if reference.is_none() && files.iter().any(|file| file.name.starts_with(ACCEPTED)) {
+        notes.push("None of the accepted submissions are in an enabled language, so the problem has no reference solution.".into());
+    }
+
+    // Samples are listed first, like Kattis does.
+    let mut pairs = BTreeMap::new();
+    for file in files {
+        let sample = file.name.starts_with("data/sample/");
+        if !sample && !file.name.starts_with("data/secret/") {
+            continue;
+        }
+
+        let (stem, ext) = match file.name.rfind('.') {
+            Some(i) => (&file.name[..i], &file.name[i + 1..]),
+            None => continue,
+        };
+
+        let pair = pairs.entry((!sample, stem.to_string())).or_insert((None, None));
+        match ext {
+            "in" => pair.0 = Some(normalize(&file.content)),
+            "ans" => pair.1 = Some(normalize(&file.content)),
+            _ => (),
+        }
+    }
+
+    let mut tests = Vec::new();
+    let mut unmatched = Vec::new();
+    for ((secret, stem), pair) in pairs {
+        match pair {
+            (Some(input), Some(output)) => tests.push((input, output, !secret)),
+            _ => unmatched.push(stem),
+        }
+    }
+
+    if !unmatched.is_empty() {
+        notes.push(format!("These tests had no matching input or answer: {}", unmatched.join(", ")));
+    }
+
+    Ok(Package {
+        name: name,
+        description: description,
+        tags: tags,
+        time_limit: time_limit,
+        memory_limit: memory_limit,
+        output_limit: output_limit,
+        checker: checker.into(),
+        tolerance: tolerance,
+        interactive: interactive,
+        validator: validator,
+        reference: reference,
+        tests: tests,
+        notes: notes,
+    })
+}
+
+/// Files from other systems may have windows line endings.
+fn normalize(text: &str) -> String {
+    text.replace("\r\n", "\n")
+}
+
+/// Gets a name, which the newer version of the format allows in several
+/// languages.
+fn text(value: &Value) -> Option<String> {
+    let value = match *value {
+        Value::Mapping(ref names) => names.get(&Value::String("en".into()))
+            .or_else(|| names.iter().map(|(_, name)| name).next())?,
+        ref value => value,
+    };
+
+    value.as_str()
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+/// Gets the name from `\problemname{...}` in a LaTeX statement.
+fn problem_name(statement: &str) -> Option<String> {
+    const COMMAND: &str = "\\problemname{";
+
+    let start = statement.find(COMMAND)? + COMMAND.len();
+    let end = statement[start..].find('}')?;
+    Some(statement[start..start + end].trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+/// Finds the first file in a folder that's in one of the enabled languages.
+fn program<'a>(files: &'a [SourceFile], folder: &str, languages: &[Language]) -> Option<(&'a SourceFile, i32)> {
+    files.iter()
+        .filter(|file| file.name.starts_with(folder))
+        .filter_map(|file| {
+            let ext = extension(&file.name)?;
+            languages.iter()
+                .filter(|lang| lang.enabled)
+                .find(|lang| extension(&lang.source) == Some(ext))
+                .map(|lang| (file, lang.id))
+        })
+        .next()
+}
+
+fn extension(name: &str) -> Option<&str> {
+    let name = name.rsplit('/').next().unwrap_or(name);
+    name.rfind('.').map(|i| &name[i + 1..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(name: &str, content: &str) -> SourceFile {
+        SourceFile {
+            name: name.into(),
+            content: content.into(),
+        }
+    }
+
+    fn languages() -> Vec<Language> {
+        vec![Language {
+            id: 1,
+            name: "C++".into(),
+            source: "main.cpp".into(),
+            compile: "g++ main.cpp".into(),
+            run: "./a.out".into(),
+            version: "g++ --version".into(),
+            enabled: true,
+            example: String::new(),
+            backend: "native".into(),
+        }]
+    }
+
+    #[test]
+    fn short_name_keeps_letters_and_numbers() {
+        assert_eq!(short_name("A + B, Again!"), "a-b-again");
+        assert_eq!(short_name("???"), "problem");
+    }
+
+    #[test]
+    fn import_reads_samples_before_secret_tests() {
+        let files = vec![
+            file(CONFIG, "name: Sum\nlimits:\n  time_limit: 1.5\nkeywords: [math]\n"),
+            file(STATEMENTS[0], "Add two numbers.\r\n"),
+            file("data/secret/1.in", "2 3\r\n"),
+            file("data/secret/1.ans", "5\r\n"),
+            file("data/sample/1.in", "1 1\n"),
+            file("data/sample/1.ans", "2\n"),
+        ];
+
+        let package = import(&files, &languages()).unwrap();
+        assert_eq!(package.name, "Sum");
+        assert_eq!(package.description, "Add two numbers.\n");
+        assert_eq!(package.tags, vec!["math"]);
+        assert_eq!(package.time_limit, 1500);
+        assert_eq!(package.checker, "case");
+        assert_eq!(package.tests, vec![
+            ("1 1\n".to_string(), "2\n".to_string(), true),
+            ("2 3\n".to_string(), "5\n".to_string(), false),
+        ]);
+        assert!(package.notes.is_empty());
+    }
+
+    #[test]
+    fn import_notes_tests_without_answers() {
+        let files = vec![
+            file(CONFIG, "name: Sum\n"),
+            file(STATEMENTS[0], "Add two numbers."),
+            file("data/sample/1.in", "1 1\n"),
+            file("data/secret/1.in", "2 3\n"),
+            file("data/secret/group/2.in", "4 5\n"),
+        ];
+
+        let package = import(&files, &languages()).unwrap();
+        assert!(package.tests.is_empty());
+        assert_eq!(package.notes.len(), 2);
+        assert!(package.notes[1].ends_with("data/sample/1, data/secret/1, data/secret/group/2"));
+    }
+
+    #[test]
+    fn import_needs_a_validator_for_custom_checkers() {
+        let mut files = vec![
+            file(CONFIG, "name: Sum\nvalidation: custom\n"),
+            file(STATEMENTS[0], "Add two numbers."),
+        ];
+        assert!(import(&files, &languages()).is_err());
+
+        files.push(file("output_validators/check/main.cpp", "int main() {}"));
+        let package = import(&files, &languages()).unwrap();
+        assert_eq!(package.checker, "kattis");
+        assert_eq!(package.validator, Some((1, "int main() {}".to_string())));
+    }
+}
@@ -0,0 +1,47 @@
+extern crate actix_web;
+extern crate chrono;
+extern crate comrak;
+#[macro_use] extern crate diesel;
+extern crate failure;
+extern crate futures;
+extern crate libc;
+extern crate liquid;
+#[macro_use] extern crate log;
+extern crate oauth2;
+extern crate reqwest;
+extern crate serde;
+#[macro_use] extern crate serde_derive;
+extern crate serde_json;
+extern crate serde_yaml;
+extern crate tempdir;
+#[cfg(feature = "wasm")] extern crate wasi_common;
+#[cfg(feature = "wasm")] extern crate wasmtime;
+#[cfg(feature = "wasm")] extern crate wasmtime_wasi;
+
+use diesel::SqliteConnection;
+
+pub mod db;
+mod diff;
+pub mod judge;
+mod kattis;
+pub mod middleware;
+pub mod oauth;
+mod plagiarism;
+pub mod quota;
+mod tags;
+pub mod routes;
+pub mod visibility;
+
+pub struct AppState {
+    pub db: SqliteConnection,
+    pub auth: oauth::OAuth2,
+    pub judge: judge::Judge,
+    pub runner: judge::queue::Runner,
+    /// Hands out work to judge nodes.
+    pub dispatcher: judge::queue::Dispatcher,
+    /// The token judge nodes have to send, nodes can't connect without one.
+    pub node_token: Option<String>,
+    pub visibility: visibility::Policy,
+    /// How often users can run code with custom input.
+    pub runs: quota::RunQuota,
+}
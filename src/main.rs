@@ -1,13 +1,7 @@
 extern crate actix_web;
-extern crate comrak;
-#[macro_use] extern crate diesel;
+extern crate dotenv;
 extern crate env_logger;
-extern crate failure;
-extern crate liquid;
-extern crate oauth2;
-extern crate reqwest;
-extern crate serde;
-#[macro_use] extern crate serde_derive;
+extern crate potw;
 
 use actix_web::{
     App, Responder,
@@ -18,37 +12,63 @@ use actix_web::{
         session::{ SessionStorage, CookieSessionBackend },
     },
 };
-use diesel::{ SqliteConnection, Connection };
 
-use middleware::{ CurrentUser, Liquid, Template };
-
-mod db;
-mod middleware;
-mod oauth;
-mod routes;
-
-pub struct AppState {
-    pub db: SqliteConnection,
-    pub auth: oauth::OAuth2,
-}
+use potw::{
+    AppState, db, judge, oauth, quota, routes, visibility,
+    judge::Judge,
+    middleware::{ CurrentUser, Flash, Liquid, Template },
+};
 
 fn main() {
-    std::env::set_var("RUST_LOG", "actix_web=info");
+    dotenv::dotenv().ok();
+    std::env::set_var("RUST_LOG", "actix_web=info,potw=info");
     env_logger::init();
 
-    actix_web::server::new(|| {
-            let db = SqliteConnection::establish("database.db")
+    // The judge can be configured with the environment or a .env file.
+    let judge_dir = std::env::var("POTW_JUDGE_DIR")
+        .map(Into::into)
+        .unwrap_or_else(|_| std::env::temp_dir().join("potw"));
+
+    let workers = std::env::var("POTW_JUDGE_WORKERS")
+        .ok()
+        .and_then(|workers| workers.parse().ok())
+        .unwrap_or(2);
+
+    let judge = Judge::new(judge_dir);
+    let policy = visibility::Policy::from_env();
+    let runs = quota::RunQuota::default();
+
+    let (runner, dispatcher) = judge::queue::start(workers, judge.clone())
+        .expect("Couldn't start the judge workers.");
+
+    // Judge nodes on other machines can only connect with the token.
+    let node_token = std::env::var("POTW_NODE_TOKEN")
+        .ok()
+        .filter(|token| !token.is_empty());
+
+    let addresses = std::env::var("POTW_BIND")
+        .unwrap_or_else(|_| "192.168.1.144:80".into());
+
+    let server = actix_web::server::new(move || {
+            let db = db::establish()
                 .expect("Couldn't connect to Sqlite database.");
 
             App::with_state({
                     AppState {
                         db: db,
                         auth: oauth::OAuth2::new(),
+                        judge: judge.clone(),
+                        runner: runner.clone(),
+                        dispatcher: dispatcher.clone(),
+                        node_token: node_token.clone(),
+                        visibility: policy.clone(),
+                        runs: runs.clone(),
                     }
                 })
                 .middleware(Logger::default())
                 .middleware(SessionStorage::new(CookieSessionBackend::private(&[0; 32]).secure(false)))
                 .middleware(CurrentUser)
+                .middleware(Flash)
                 .middleware({
                     ErrorHandlers::new()
                         .handler(StatusCode::NOT_FOUND, |req, _| {
@@ -60,8 +80,15 @@ fn main() {
                 .middleware(Liquid::new("./templates/"))
                 .configure(routes::configure)
                 .handler("/static", StaticFiles::new("./static/").unwrap())
-        })
-        .bind("192.168.1.144:80")
-        .unwrap()
-        .run();
+        });
+
+    // More than one address can be given separated by commas, such as a
+    // public address and a loopback address for a judge node.
+    let server = addresses.split(',')
+        .fold(server, |server, address| {
+            server.bind(address.trim())
+                .expect("Couldn't bind to the address.")
+        });
+
+    server.run();
 }
@@ -1,6 +1,6 @@
 use actix_web::{
-    Result, error::ErrorInternalServerError,
-    HttpRequest, HttpResponse,
+    Result, FromRequest, HttpRequest, HttpResponse,
+    error::{ ErrorInternalServerError, ErrorUnauthorized },
     middleware::{
         Middleware, Started, Response,
         session::RequestSession,
@@ -65,3 +65,17 @@ impl Middleware<AppState> for CurrentUser {
         Ok(Response::Done(resp))
     }
 }
+
+/// Allows handlers to require a logged in user by taking the `User` as an
+/// extractor. Requests without a user are rejected.
+impl<S> FromRequest<S> for User {
+    type Config = ();
+    type Result = Result<Self>;
+
+    fn from_request(req: &HttpRequest<S>, _: &Self::Config) -> Self::Result {
+        req.extensions()
+            .get::<User>()
+            .cloned()
+            .ok_or_else(|| ErrorUnauthorized("You must be logged in."))
+    }
+}
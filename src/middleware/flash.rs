@@ -0,0 +1,60 @@
+use actix_web::{
+    Result, HttpRequest, HttpResponse,
+    middleware::{
+        Middleware, Started, Response,
+        session::RequestSession,
+    },
+};
+use liquid::Value;
+
+use ::middleware::Template;
+
+/// Messages left in the session by a previous request, usually right
+/// before a redirect.
+struct Messages {
+    success: Option<String>,
+    error: Option<String>,
+}
+
+/// Moves the `success` and `error` messages stored in the session into the
+/// template of the next rendered page so they are only ever shown once.
+pub struct Flash;
+
+impl<S> Middleware<S> for Flash {
+    fn start(&self, req: &HttpRequest<S>) -> Result<Started> {
+        // The session is written back before our response is handled so the
+        // messages have to be taken out of the session here.
+        let session = req.session();
+        let success = session.get::<String>("success")?;
+        let error = session.get::<String>("error")?;
+
+        if success.is_some() || error.is_some() {
+            session.remove("success");
+            session.remove("error");
+            req.extensions_mut().insert(Messages { success: success, error: error });
+        }
+
+        Ok(Started::Done)
+    }
+
+    fn response(&self, req: &HttpRequest<S>, resp: HttpResponse) -> Result<Response> {
+        let mut ext = req.extensions_mut();
+
+        if ext.get::<Template>().is_some() {
+            if let Some(messages) = ext.remove::<Messages>() {
+                // We already checked if a template exists so it's fine for us to unwrap.
+                let globals = &mut ext.get_mut::<Template>().unwrap().globals;
+
+                if let Some(success) = messages.success {
+                    globals.insert("success".into(), Value::scalar(success));
+                }
+
+                if let Some(error) = messages.error {
+                    globals.insert("error".into(), Value::scalar(error));
+                }
+            }
+        }
+
+        Ok(Response::Done(resp))
+    }
+}
@@ -1,9 +1,9 @@
 pub use self::admin::*;
 pub use self::currentuser::*;
+pub use self::flash::*;
 pub use self::liquid::*;
 
 mod admin;
 mod currentuser;
+mod flash;
 mod liquid;
-
-
@@ -0,0 +1,407 @@
+//! Finds solutions that look like they were copied from each other.
+//!
+//! Code is broken into tokens with names, numbers and strings replaced by
+//! placeholders, so renaming variables or reformatting the code doesn't hide
+//! a copy. Runs of tokens are hashed and some of the hashes are picked as the
+//! code's fingerprints by winnowing, solutions that share a lot of
+//! fingerprints share a lot of code.
+
+use std::collections::HashSet;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{ Hash, Hasher };
+use std::ops::Range;
+
+use liquid::{ Object, Value };
+
+use judge::SourceFile;
+
+/// How many tokens in a row are hashed together. Shorter runs of shared
+/// tokens are ignored as coincidence.
+const NOISE: usize = 8;
+
+/// How many hashes each fingerprint is picked from. Shared runs of at least
+/// `NOISE + WINDOW - 1` tokens are always found.
+const WINDOW: usize = 5;
+
+/// What the tokenizer needs to know about a language.
+struct Syntax {
+    line_comments: &'static [&'static str],
+    block_comments: &'static [(&'static str, &'static str)],
+    /// Whether `'` only starts character literals, like in C, so it isn't
+    /// mistaken for a string in Rust's lifetimes.
+    char_literals: bool,
+    /// Words that are kept as they are instead of being treated as names.
+    keywords: &'static [&'static str],
+}
+
+const C_LIKE: Syntax = Syntax {
+    line_comments: &["//"],
+    block_comments: &[("/*", "*/")],
+    char_literals: true,
+    keywords: &[
+        "auto", "bool", "boolean", "break", "byte", "case", "catch", "char", "class", "const",
+        "continue", "default", "defer", "delete", "do", "double", "else", "enum", "extends",
+        "false", "final", "float", "fn", "for", "func", "function", "go", "if", "impl",
+        "implements", "import", "in", "include", "int", "interface", "let", "long", "loop",
+        "match", "mut", "namespace", "new", "null", "nullptr", "package", "private",
+        "protected", "public", "range", "return", "short", "signed", "sizeof", "static",
+        "string", "struct", "super", "switch", "template", "this", "throw", "throws", "true",
+        "try", "typedef", "unsigned", "using", "var", "void", "while",
+    ],
+};
+
+const PYTHON: Syntax = Syntax {
+    line_comments: &["#"],
+    block_comments: &[],
+    char_literals: false,
+    keywords: &[
+        "and", "as", "break", "class", "continue", "def", "del", "elif", "else", "except",
+        "False", "finally", "for", "from", "global", "if", "import", "in", "input", "int",
+        "is", "lambda", "len", "None", "nonlocal", "not", "or", "pass", "print", "raise",
+        "range", "return", "True", "try", "while", "with", "yield",
+    ],
+};
+
+const RUBY: Syntax = Syntax {
+    line_comments: &["#"],
+    block_comments: &[("=begin", "=end")],
+    char_literals: false,
+    keywords: &[
+        "and", "begin", "break", "case", "class", "def", "do", "each", "else", "elsif", "end",
+        "ensure", "false", "for", "gets", "if", "in", "loop", "module", "next", "nil", "not",
+        "or", "puts", "rescue", "return", "self", "then", "times", "true", "unless", "until",
+        "when", "while", "yield",
+    ],
+};
+
+const HASKELL: Syntax = Syntax {
+    line_comments: &["--"],
+    block_comments: &[("{-", "-}")],
+    char_literals: true,
+    keywords: &[
+        "case", "class", "data", "deriving", "do", "else", "if", "import", "in", "instance",
+        "let", "main", "module", "newtype", "of", "then", "type", "where",
+    ],
+};
+
+impl Syntax {
+    /// Picks the syntax of a file from its extension, anything unknown is
+    /// read like C.
+    fn for_file(name: &str) -> &'static Syntax {
+        let ext = name.rsplit('.').next().unwrap_or("").to_lowercase();
+        match &ext[..] {
+            "py" => &PYTHON,
+            "rb" => &RUBY,
+            "hs" => &HASKELL,
+            _ => &C_LIKE,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Token {
+    /// A hash of the token after names, numbers and strings are replaced.
+    kind: u64,
+    /// The index of the file the token is in.
+    file: usize,
+    /// Where the token is in its file.
+    span: Range<usize>,
+}
+
+/// The fingerprints of a solution.
+pub struct Fingerprint {
+    tokens: Vec<Token>,
+    /// The hash of every run of tokens, by the position of its first token.
+    runs: Vec<u64>,
+    /// The hashes picked by winnowing.
+    picked: HashSet<u64>,
+}
+
+impl Fingerprint {
+    pub fn new(files: &[SourceFile]) -> Self {
+        let mut tokens = Vec::new();
+        for (i, file) in files.iter().enumerate() {
+            tokenize(i, &file.content, Syntax::for_file(&file.name), &mut tokens);
+        }
+
+        let runs = tokens.windows(NOISE)
+            .map(|run| hash(&run.iter().map(|token| token.kind).collect::<Vec<_>>()))
+            .collect::<Vec<_>>();
+
+        let picked = winnow(&runs);
+
+        Fingerprint {
+            tokens: tokens,
+            runs: runs,
+            picked: picked,
+        }
+    }
+
+    /// How much of the smaller of the two solutions is shared with the
+    /// other, from 0 to 1.
+    pub fn similarity(&self, other: &Fingerprint) -> f64 {
+        let smaller = self.picked.len().min(other.picked.len());
+        if smaller == 0 {
+            return 0.0;
+        }
+
+        self.picked.intersection(&other.picked).count() as f64 / smaller as f64
+    }
+
+    /// Splits the files this fingerprint was made from into the parts that
+    /// are shared with the other solution and the parts that aren't.
+    pub fn highlight<'a>(&self, files: &'a [SourceFile], other: &Fingerprint) -> Vec<Vec<Part<'a>>> {
+        let theirs = other.runs.iter().collect::<HashSet<_>>();
+
+        let mut shared = vec![false; self.tokens.len()];
+        for (i, run) in self.runs.iter().enumerate() {
+            if theirs.contains(run) {
+                for token in &mut shared[i..i + NOISE] {
+                    *token = true;
+                }
+            }
+        }
+
+        // The shared tokens of each file, with tokens next to each other
+        // merged together.
+        let mut ranges = vec![Vec::<Range<usize>>::new(); files.len()];
+        let mut previous = None;
+        for (i, token) in self.tokens.iter().enumerate().filter(|&(i, _)| shared[i]) {
+            let file = &mut ranges[token.file];
+            let follows = i > 0 && previous == Some(i - 1) && self.tokens[i - 1].file == token.file;
+
+            if follows {
+                file.last_mut().unwrap().end = token.span.end;
+            } else {
+                file.push(token.span.clone());
+            }
+            previous = Some(i);
+        }
+
+        files.iter()
+            .zip(ranges)
+            .map(|(file, ranges)| {
+                let content = &file.content[..];
+                let mut parts = Vec::new();
+                let mut at = 0;
+                for range in ranges {
+                    if range.start > at {
+                        parts.push(Part { text: &content[at..range.start], shared: false });
+                    }
+                    parts.push(Part { text: &content[range.clone()], shared: true });
+                    at = range.end;
+                }
+                if at < content.len() {
+                    parts.push(Part { text: &content[at..], shared: false });
+                }
+                parts
+            })
+            .collect()
+    }
+}
+
+/// A piece of a file, shared with another solution or not.
+#[derive(Clone, Copy, Debug)]
+pub struct Part<'a> {
+    pub text: &'a str,
+    pub shared: bool,
+}
+
+impl<'a> Part<'a> {
+    pub fn to_liquid(&self) -> Object {
+        let mut obj = Object::new();
+        obj.insert("text".into(), Value::scalar(self.text.to_string()));
+        obj.insert("shared".into(), Value::scalar(self.shared));
+        obj
+    }
+}
+
+fn hash<T: Hash + ?Sized>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Picks the smallest hash of every window of hashes, taking the rightmost
+/// one on ties so the same hash is picked by windows next to each other.
+fn winnow(runs: &[u64]) -> HashSet<u64> {
+    let mut picked = HashSet::new();
+    if runs.is_empty() {
+        return picked;
+    }
+
+    let window = WINDOW.min(runs.len());
+    for hashes in runs.windows(window) {
+        let smallest = hashes.iter()
+            .enumerate()
+            .min_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(&a.0)))
+            .map(|(_, &hash)| hash)
+            .unwrap();
+
+        picked.insert(smallest);
+    }
+
+    picked
+}
+
+/// Breaks a file into tokens, skipping whitespace and comments.
+fn tokenize(file: usize, content: &str, syntax: &Syntax, tokens: &mut Vec<Token>) {
+    let mut i = 0;
+    while i < content.len() {
+        let rest = &content[i..];
+        let c = rest.chars().next().unwrap();
+
+        if c.is_whitespace() {
+            i += c.len_utf8();
+            continue;
+        }
+
+        if syntax.line_comments.iter().any(|start| rest.starts_with(start)) {
+            i += rest.find('\n').unwrap_or(rest.len());
+            continue;
+        }
+
+        if let Some(&(start, end)) = syntax.block_comments.iter().find(|&&(start, _)| rest.starts_with(start)) {
+            i += rest[start.len()..].find(end)
+                .map(|len| start.len() + len + end.len())
+                .unwrap_or(rest.len());
+            continue;
+        }
+
+        let (len, kind) = if c.is_alphabetic() || c == '_' {
+            let len = rest.find(|c: char| !c.is_alphanumeric() && c != '_').unwrap_or(rest.len());
+            let word = &rest[..len];
+            (len, if syntax.keywords.contains(&word) { word } else { "<name>" })
+        } else if c.is_ascii_digit() {
+            let len = rest.find(|c: char| !c.is_alphanumeric() && c != '.' && c != '_').unwrap_or(rest.len());
+            (len, "<number>")
+        } else if c == '"' || c == '`' || (c == '\'' && (!syntax.char_literals || is_char_literal(rest))) {
+            (literal_len(rest, c), "<string>")
+        } else {
+            (c.len_utf8(), &rest[..c.len_utf8()])
+        };
+
+        tokens.push(Token {
+            kind: hash(kind),
+            file: file,
+            span: i..i + len,
+        });
+        i += len;
+    }
+}
+
+/// Checks if a `'` starts a character literal such as `'a'` or `'\n'`.
+fn is_char_literal(rest: &str) -> bool {
+    let mut chars = rest.chars().skip(1);
+    match chars.next() {
+        Some('\\') => true,
+        Some(_) => chars.next() == Some('\''),
+        None => false,
+    }
+}
+
+/// Finds the length of a string or character literal, including its quotes.
+/// Literals that aren't closed end at the end of their line.
+fn literal_len(rest: &str, quote: char) -> usize {
+    let triple = quote.to_string().repeat(3);
+    if rest.starts_with(&triple) {
+        return rest[3..].find(&triple[..])
+            .map(|len| 3 + len + 3)
+            .unwrap_or(rest.len());
+    }
+
+    let mut escaped = false;
+    for (i, c) in rest.char_indices().skip(1) {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == quote {
+            return i + 1;
+        } else if c == '\n' && quote != '`' {
+            return i;
+        }
+    }
+
+    rest.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SUM: &str = "#include <cstdio>
+
+int main() {
+    int n, total = 0;
+    scanf(\"%d\", &n);
+    for (int i = 0; i < n; i++) {
+        int x;
+        scanf(\"%d\", &x);
+        total += x;
+    }
+    printf(\"%d\\n\", total);
+}
+";
+
+    /// `SUM` with every name changed, reformatted and commented.
+    const RENAMED: &str = "#include <cstdio>
+// Adds up the numbers.
+int main()
+{
+    int count, sum = 0;
+    scanf(\"%d\", &count);
+    for (int j = 0; j < count; j++)
+    {
+        int value;   /* the next one */
+        scanf(\"%d\", &value);
+        sum += value;
+    }
+    printf(\"%d\\n\", sum);
+}
+";
+
+    const PYTHON: &str = "import sys
+words = sys.stdin.read().split()
+print(max(words, key=len) if words else 'none')
+";
+
+    fn fingerprint(name: &str, content: &str) -> Fingerprint {
+        Fingerprint::new(&[SourceFile {
+            name: name.into(),
+            content: content.into(),
+        }])
+    }
+
+    #[test]
+    fn identical_code_is_fully_similar() {
+        let sum = fingerprint("main.cpp", SUM);
+        assert_eq!(sum.similarity(&fingerprint("main.cpp", SUM)), 1.0);
+    }
+
+    #[test]
+    fn renaming_and_reformatting_is_still_similar() {
+        let sum = fingerprint("main.cpp", SUM);
+        assert_eq!(sum.similarity(&fingerprint("sum.cpp", RENAMED)), 1.0);
+    }
+
+    #[test]
+    fn different_code_isnt_similar() {
+        let sum = fingerprint("main.cpp", SUM);
+        assert!(sum.similarity(&fingerprint("main.py", PYTHON)) < 0.2);
+        assert_eq!(sum.similarity(&fingerprint("main.cpp", "")), 0.0);
+    }
+
+    #[test]
+    fn highlight_covers_the_whole_copy() {
+        let files = [SourceFile {
+            name: "main.cpp".into(),
+            content: SUM.into(),
+        }];
+        let parts = Fingerprint::new(&files).highlight(&files, &fingerprint("sum.cpp", RENAMED));
+
+        assert_eq!(parts.len(), 1);
+        assert!(parts[0].iter().all(|part| part.shared || part.text.trim().is_empty()));
+        assert_eq!(parts[0].iter().map(|part| part.text).collect::<String>(), SUM);
+    }
+}
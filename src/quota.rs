@@ -0,0 +1,175 @@
+//! Limits how often users can submit solutions to a problem or run code
+//! with custom input so they can't flood the judge. Admins aren't limited.
+
+use std::collections::HashMap;
+use std::sync::{ Arc, Mutex };
+
+use chrono::{ Duration, NaiveDateTime, Utc };
+use diesel::prelude::*;
+use liquid::{ Object, Value };
+
+use ::{
+    db::{
+        models::{ format_time, Problem, User },
+        schema::solution,
+    },
+    judge::Verdict,
+};
+
+/// How many seconds users have to wait between runs with custom input.
+const RUN_COOLDOWN: i64 = 10;
+
+/// What's left of a user's submissions to a problem.
+pub struct Quota {
+    /// Submissions left in total, `None` if there's no limit.
+    pub attempts: Option<i64>,
+    /// Submissions left today, `None` if there's no limit.
+    pub today: Option<i64>,
+    /// When the user can submit again if they have to wait.
+    pub wait_until: Option<NaiveDateTime>,
+}
+
+impl Quota {
+    pub fn check(db: &SqliteConnection, user: &User, problem: &Problem) -> QueryResult<Self> {
+        if user.admin {
+            return Ok(Quota { attempts: None, today: None, wait_until: None });
+        }
+
+        let now = Utc::now().naive_utc();
+
+        // Solutions the judge failed on weren't the user's fault, so they
+        // don't use up attempts or start the cooldown.
+        let attempts = match problem.max_attempts {
+            Some(max) => {
+                let used = solution::table
+                    .filter(solution::uid.eq(user.id))
+                    .filter(solution::pid.eq(problem.id))
+                    .filter(solution::verdict.ne(Verdict::JudgeError.code()))
+                    .count()
+                    .get_result::<i64>(db)?;
+
+                Some((max as i64 - used).max(0))
+            }
+            None => None,
+        };
+
+        let today = match problem.daily_attempts {
+            Some(max) => {
+                let midnight = now.date().and_hms(0, 0, 0);
+                let used = solution::table
+                    .filter(solution::uid.eq(user.id))
+                    .filter(solution::pid.eq(problem.id))
+                    .filter(solution::submitted_at.ge(midnight))
+                    .filter(solution::verdict.ne(Verdict::JudgeError.code()))
+                    .count()
+                    .get_result::<i64>(db)?;
+
+                Some((max as i64 - used).max(0))
+            }
+            None => None,
+        };
+
+        // Pending solutions count as failed so the cooldown can't be skipped
+        // by submitting again before the judge gets to them.
+        let wait_until = match problem.cooldown {
+            Some(cooldown) => {
+                let last = solution::table
+                    .filter(solution::uid.eq(user.id))
+                    .filter(solution::pid.eq(problem.id))
+                    .order(solution::submitted_at.desc())
+                    .select((solution::verdict, solution::submitted_at))
+                    .first::<(String, NaiveDateTime)>(db)
+                    .optional()?;
+
+                last.and_then(|(verdict, submitted_at)| {
+                    let until = submitted_at + Duration::seconds(cooldown as i64);
+                    let failed = verdict != Verdict::Accepted.code() && verdict != Verdict::JudgeError.code();
+                    if failed && until > now {
+                        Some(until)
+                    } else {
+                        None
+                    }
+                })
+            }
+            None => None,
+        };
+
+        Ok(Quota {
+            attempts: attempts,
+            today: today,
+            wait_until: wait_until,
+        })
+    }
+
+    /// Explains why the user can't submit right now.
+    pub fn error(&self) -> Option<String> {
+        if self.attempts == Some(0) {
+            Some("You've used all of your attempts for this problem.".into())
+        } else if self.today == Some(0) {
+            Some("You've used all of your attempts for today, try again tomorrow.".into())
+        } else if let Some(until) = self.wait_until {
+            Some(format!("You have to wait another {} before submitting again.", wait(until)))
+        } else {
+            None
+        }
+    }
+
+    pub fn to_liquid(&self) -> Object {
+        let mut obj = Object::new();
+        if let Some(attempts) = self.attempts {
+            obj.insert("attempts".into(), Value::scalar(attempts as i32));
+        }
+        if let Some(today) = self.today {
+            obj.insert("today".into(), Value::scalar(today as i32));
+        }
+        if let Some(until) = self.wait_until {
+            obj.insert("wait_until".into(), Value::scalar(format_time(&until)));
+        }
+        if let Some(error) = self.error() {
+            obj.insert("error".into(), Value::scalar(error));
+        }
+        obj
+    }
+}
+
+/// Keeps track of when users last ran code with custom input. Runs aren't
+/// saved anywhere, so this is only kept for as long as the server is up.
+#[derive(Clone, Default)]
+pub struct RunQuota {
+    last: Arc<Mutex<HashMap<i32, NaiveDateTime>>>,
+}
+
+impl RunQuota {
+    /// Records a run for the user, or explains why they have to wait
+    /// before running code again.
+    pub fn take(&self, user: &User) -> Result<(), String> {
+        if user.admin {
+            return Ok(());
+        }
+
+        let now = Utc::now().naive_utc();
+        let cooldown = Duration::seconds(RUN_COOLDOWN);
+        let mut last = self.last.lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        // Users that have waited long enough are forgotten.
+        last.retain(|_, ran_at| *ran_at + cooldown > now);
+
+        if let Some(&ran_at) = last.get(&user.id) {
+            return Err(format!("You have to wait another {} before running code again.", wait(ran_at + cooldown)));
+        }
+
+        last.insert(user.id, now);
+        Ok(())
+    }
+}
+
+/// Says how long it is until a time, such as "30 seconds".
+fn wait(until: NaiveDateTime) -> String {
+    let seconds = (until - Utc::now().naive_utc()).num_seconds().max(1);
+    if seconds < 120 {
+        format!("{} seconds", seconds)
+    } else {
+        format!("{} minutes", (seconds + 59) / 60)
+    }
+}
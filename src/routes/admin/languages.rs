@@ -1,29 +1,32 @@
 use actix_web::{
-    Result,
+    Result, Error,
     error::{ ErrorInternalServerError, ErrorNotFound },
     Scope, Responder, State, Path, Form,
-    HttpResponse,
+    Either, HttpResponse,
+    middleware::session::Session,
 };
 use diesel::{ self, prelude::* };
+use futures::{ future, Future };
 use liquid::{ Object, Value };
 
 use ::{
     AppState,
     db::{
         models::Language,
-        schema::language,
+        schema::{ language, problem, problem_language, solution },
     },
+    judge::{ Backend, Limits, Toolchain, queue::Runner },
     middleware::Template,
 };
 
 pub fn configure(scope: Scope<AppState>) -> Scope<AppState> {
     scope.resource("/create", |r| {
             r.get().with(create);
-            r.post().with(create_form);
+            r.post().with_async(create_form);
         })
         .resource("/{id}/edit", |r| {
             r.get().with(edit);
-            r.post().with(edit_form);
+            r.post().with_async(edit_form);
         })
         .resource("/{id}/delete", |r| r.with(delete))
         .resource("/{id}/delete/confirm", |r| r.with(delete_confirm))
@@ -32,21 +35,130 @@ pub fn configure(scope: Scope<AppState>) -> Scope<AppState> {
 #[derive(Deserialize)]
 struct LanguageForm {
     name: String,
+    source: String,
+    compile: String,
+    run: String,
+    version: String,
+    enabled: Option<String>,
+    example: String,
+    backend: String,
+}
+
+impl LanguageForm {
+    fn into_language(self, id: i32) -> Language {
+        Language {
+            id: id,
+            name: self.name,
+            source: self.source.trim().into(),
+            compile: self.compile.trim().into(),
+            run: self.run.trim().into(),
+            version: self.version,
+            enabled: self.enabled.is_some(),
+            example: self.example.replace("\r\n", "\n"),
+            backend: self.backend,
+        }
+    }
+}
+
+/// Makes sure an enabled language can compile and run its hello world
+/// program before it's saved. The program is run by the judge workers.
+fn validate(db: &SqliteConnection, runner: &Runner, language: &Language) -> Box<Future<Item = (), Error = String>> {
+    if Backend::from_name(&language.backend).is_none() {
+        return Box::new(future::err("Unknown backend.".to_string()));
+    }
+
+    if !language.enabled {
+        return Box::new(future::ok(()));
+    }
+
+    let toolchain = match Toolchain::for_language(language) {
+        Some(toolchain) => toolchain,
+        None => return Box::new(future::err("A source file and run command are required to enable a language.".to_string())),
+    };
+
+    if language.example.trim().is_empty() {
+        return Box::new(future::err("A hello world program is required to enable a language.".to_string()));
+    }
+
+    let resp = runner.run(db, toolchain, language.example.clone(), String::new(), Limits::default())
+        .map_err(|err| format!("The judge failed to run the hello world program: {}", err))
+        .and_then(|run| {
+            if let Some(log) = run.compile_error {
+                return Err(format!("The hello world program failed to compile: {}", log));
+            }
+
+            match run.execution {
+                Some(ref exec) if exec.success() => Ok(()),
+                Some(ref exec) => Err(format!("The hello world program failed to run. {} {}", exec.describe(), run.stderr)),
+                None => Err("The hello world program didn't run.".into()),
+            }
+        });
+
+    Box::new(resp)
+}
+
+/// Shows the form again with the values that were entered and what was
+/// wrong with them.
+fn invalid(language: Language, editing: bool, error: String) -> Template {
+    let mut language = language.to_liquid();
+    if !editing {
+        language.remove("id");
+    }
+
+    let mut obj = Object::new();
+    obj.insert("language".into(), Value::Object(language));
+    obj.insert("error".into(), Value::scalar(error));
+    obj.insert("backends".into(), backends());
+    Template::render("admin/language.liquid", obj)
+}
+
+/// The backends a language can pick from.
+fn backends() -> Value {
+    let backends = Backend::NAMES.iter()
+        .map(|&(name, description)| {
+            let mut obj = Object::new();
+            obj.insert("name".into(), Value::scalar(name));
+            obj.insert("description".into(), Value::scalar(description));
+            Value::Object(obj)
+        });
+
+    Value::array(backends)
 }
 
 fn create(_state: State<AppState>) -> impl Responder {
-    Template::render("admin/language.liquid", None)
+    let mut obj = Object::new();
+    obj.insert("backends".into(), backends());
+    Template::render("admin/language.liquid", obj)
 }
 
-fn create_form((state, form): (State<AppState>, Form<LanguageForm>)) -> Result<impl Responder> {
-    let LanguageForm { name } = form.into_inner();
+fn create_form((state, form): (State<AppState>, Form<LanguageForm>)) -> Box<Future<Item = Either<Template, HttpResponse>, Error = Error>> {
+    let lang = form.into_inner().into_language(0);
 
-    diesel::insert_into(language::table)
-        .values(language::name.eq(name))
-        .execute(&state.db)
-        .map_err(ErrorInternalServerError)?;
+    let valid = validate(&state.db, &state.runner, &lang);
+    let resp = valid
+        .then(move |valid| -> Result<_> {
+            if let Err(error) = valid {
+                return Ok(Either::A(invalid(lang, false, error)));
+            }
 
-    Ok(HttpResponse::Found().header("location", "/admin/").finish())
+            diesel::insert_into(language::table)
+                .values((
+                    language::name.eq(lang.name),
+                    language::source.eq(lang.source),
+                    language::compile.eq(lang.compile),
+                    language::run.eq(lang.run),
+                    language::version.eq(lang.version),
+                    language::enabled.eq(lang.enabled),
+                    language::example.eq(lang.example),
+                    language::backend.eq(lang.backend),
+                ))
+                .execute(&state.db)
+                .map_err(ErrorInternalServerError)?;
+
+            Ok(Either::B(HttpResponse::Found().header("location", "/admin/").finish()))
+        });
+
+    Box::new(resp)
 }
 
 
@@ -60,18 +172,39 @@ fn edit((state, id): (State<AppState>, Path<i32>)) -> Result<impl Responder> {
 
     let mut obj = Object::new();
     obj.insert("language".into(), Value::Object(language.to_liquid()));
+    obj.insert("backends".into(), backends());
     Ok(Template::render("admin/language.liquid", obj))
 }
 
-fn edit_form((state, id, form): (State<AppState>, Path<i32>, Form<LanguageForm>)) -> Result<impl Responder> {
-    let LanguageForm { name } = form.into_inner();
+fn edit_form((state, id, form): (State<AppState>, Path<i32>, Form<LanguageForm>)) -> Box<Future<Item = Either<Template, HttpResponse>, Error = Error>> {
+    let id = *id;
+    let lang = form.into_inner().into_language(id);
 
-    diesel::update(language::table.filter(language::id.eq(*id)))
-        .set(language::name.eq(name))
-        .execute(&state.db)
-        .map_err(ErrorInternalServerError)?;
+    let valid = validate(&state.db, &state.runner, &lang);
+    let resp = valid
+        .then(move |valid| -> Result<_> {
+            if let Err(error) = valid {
+                return Ok(Either::A(invalid(lang, true, error)));
+            }
 
-    Ok(HttpResponse::Found().header("location", "/admin/").finish())
+            diesel::update(language::table.filter(language::id.eq(id)))
+                .set((
+                    language::name.eq(lang.name),
+                    language::source.eq(lang.source),
+                    language::compile.eq(lang.compile),
+                    language::run.eq(lang.run),
+                    language::version.eq(lang.version),
+                    language::enabled.eq(lang.enabled),
+                    language::example.eq(lang.example),
+                    language::backend.eq(lang.backend),
+                ))
+                .execute(&state.db)
+                .map_err(ErrorInternalServerError)?;
+
+            Ok(Either::B(HttpResponse::Found().header("location", "/admin/").finish()))
+        });
+
+    Box::new(resp)
 }
 
 fn delete((state, id): (State<AppState>, Path<i32>)) -> Result<impl Responder> {
@@ -88,10 +221,42 @@ fn delete((state, id): (State<AppState>, Path<i32>)) -> Result<impl Responder> {
     Ok(Template::render("confirm.liquid", obj))
 }
 
-fn delete_confirm((state, id): (State<AppState>, Path<i32>)) -> Result<impl Responder> {
-    diesel::delete(language::table.filter(language::id.eq(*id)))
-        .execute(&state.db)
+/// Deletes a language unless problems or solutions still use it, they
+/// couldn't be judged or shown without it.
+fn delete_confirm((state, session, id): (State<AppState>, Session, Path<i32>)) -> Result<impl Responder> {
+    let db = &state.db;
+    let lid = *id;
+
+    let deleted = db.transaction::<_, diesel::result::Error, _>(|| {
+            let problems = problem::table
+                .filter(problem::checker_language.eq(lid)
+                    .or(problem::interactor_language.eq(lid))
+                    .or(problem::reference_language.eq(lid)))
+                .count()
+                .get_result::<i64>(db)?;
+
+            let solutions = solution::table
+                .filter(solution::language.eq(lid))
+                .count()
+                .get_result::<i64>(db)?;
+
+            if problems > 0 || solutions > 0 {
+                return Ok(false);
+            }
+
+            diesel::delete(problem_language::table.filter(problem_language::lid.eq(lid)))
+                .execute(db)?;
+
+            diesel::delete(language::table.filter(language::id.eq(lid)))
+                .execute(db)?;
+
+            Ok(true)
+        })
         .map_err(ErrorInternalServerError)?;
 
+    if !deleted {
+        session.set("error", "The language can't be deleted while problems or solutions use it, disable it instead.")?;
+    }
+
     Ok(HttpResponse::Found().header("location", "/admin/").finish())
 }
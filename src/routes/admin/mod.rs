@@ -8,21 +8,28 @@ use liquid::{ Object, Value };
 use ::{
     AppState,
     db::{
-        models::{ User, Problem, Language },
-        schema::{ user, problem, language },
+        models::{ JudgeNode, User, Problem, Language },
+        schema::{ judge_node, user, problem, language },
     },
     middleware::{ Admin, Template },
 };
 
 mod languages;
+mod packages;
+mod plagiarism;
 mod problems;
+mod rejudges;
+mod revisions;
+mod tests;
 
 pub fn configure(app: App<AppState>) -> App<AppState> {
     app.scope("/admin", |s| {
             s.middleware(Admin)
                 .resource("/", |r| r.with(index))
                 .nested("/languages", |s| self::languages::configure(s))
+                .nested("/plagiarism", |s| self::plagiarism::configure(s))
                 .nested("/problems", |s| self::problems::configure(s))
+                .nested("/rejudges", |s| self::rejudges::configure(s))
                 .resource("/promote/{id}", |r| r.with(promote))
                 .resource("/demote/{id}", |r| r.with(demote))
         })
@@ -47,8 +54,16 @@ fn index(state: State<AppState>) -> Result<impl Responder> {
         .into_iter()
         .map(|lang| Value::Object(lang.to_liquid()));
 
+    let nodes = judge_node::table
+        .order(judge_node::name.asc())
+        .load::<JudgeNode>(&state.db)
+        .map_err(ErrorInternalServerError)?
+        .into_iter()
+        .map(|node| Value::Object(node.to_liquid()));
+
     let mut obj = Object::new();
     obj.insert("users".into(), Value::array(users));
+    obj.insert("nodes".into(), Value::array(nodes));
     obj.insert("problems".into(), Value::array(problems));
     obj.insert("languages".into(), Value::array(languages));
 
@@ -0,0 +1,185 @@
+use actix_web::{
+    Result, Error, FromRequest, Responder, State, Path, HttpRequest, HttpResponse,
+    error::{ ErrorInternalServerError, ErrorNotFound },
+    middleware::session::RequestSession,
+};
+use diesel::{ self, prelude::* };
+use futures::{ future, Future };
+
+use ::{
+    AppState,
+    db::{
+        last_insert_rowid,
+        models::{ Language, Problem, ProblemState, User },
+        schema::{ language, problem, test_case },
+    },
+    judge::{ MAX_PACKAGE_SIZE, Convention, SourceFile, is_archive },
+    kattis::{ self, Package },
+    middleware::Template,
+    routes::multipart,
+    tags,
+};
+
+/// The largest package we'll accept, compressed packages are smaller than
+/// the files they hold.
+const UPLOAD_LIMIT: usize = MAX_PACKAGE_SIZE as usize;
+
+fn redirect() -> HttpResponse {
+    HttpResponse::Found()
+        .header("location", "/admin/problems/import")
+        .finish()
+}
+
+/// Downloads a problem as a zip of its package.
+pub fn export((req, id): (HttpRequest<AppState>, Path<i32>)) -> Result<HttpResponse> {
+    let state = req.state();
+    let problem = problem::table
+        .filter(problem::id.eq(*id))
+        .first::<Problem>(&state.db)
+        .optional()
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorNotFound("No problem found."))?;
+
+    if let Err(error) = kattis::exportable(&problem) {
+        req.session().set("error", error)?;
+        return Ok(HttpResponse::Found()
+            .header("location", format!("/admin/problems/{}/tests", problem.id))
+            .finish());
+    }
+
+    let files = kattis::export(&state.db, &problem)
+        .map_err(ErrorInternalServerError)?;
+
+    let folder = kattis::short_name(&problem.name);
+    let archive = state.judge.pack(&folder, &files)
+        .map_err(ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/zip")
+        .header("content-disposition", format!("attachment; filename=\"{}.zip\"", folder))
+        .body(archive))
+}
+
+pub fn import(_: State<AppState>) -> impl Responder {
+    Template::render("admin/import.liquid", None)
+}
+
+/// Creates a draft problem from an uploaded package. Anything that couldn't
+/// be imported as it was is shown to the admin as an error.
+pub fn import_form(req: HttpRequest<AppState>) -> Box<Future<Item = HttpResponse, Error = Error>> {
+    let resp = multipart::collect(&req, UPLOAD_LIMIT)
+        .and_then(move |parts| {
+            // An empty file input is still sent, without a file name.
+            let upload = parts.into_iter()
+                .filter(|part| part.name == "package")
+                .find(|part| part.filename.as_ref().map(|name| !name.is_empty()).unwrap_or(false));
+
+            // Packages are extracted off of the handler's thread.
+            let files: Box<Future<Item = Vec<SourceFile>, Error = String>> = match upload {
+                Some(upload) => {
+                    let filename = upload.filename.unwrap_or_default();
+                    if is_archive(&filename) {
+                        Box::new(req.state().judge.extract_package(filename, upload.data)
+                            .map_err(|err| err.to_string()))
+                    } else {
+                        Box::new(future::err("Packages have to be uploaded as zip or tar archives.".to_string()))
+                    }
+                }
+                None => Box::new(future::err("Choose a package to import.".to_string())),
+            };
+
+            files.then(move |files| import_files(&req, files))
+        });
+
+    Box::new(resp)
+}
+
+/// Imports the files of an uploaded package.
+fn import_files(req: &HttpRequest<AppState>, files: ::std::result::Result<Vec<SourceFile>, String>) -> Result<HttpResponse> {
+    let state = req.state();
+    let user = User::extract(req)?;
+    let session = req.session();
+
+    let files = match files {
+        Ok(files) => files,
+        Err(error) => {
+            session.set("error", error)?;
+            return Ok(redirect());
+        }
+    };
+
+    let languages = language::table
+        .load::<Language>(&state.db)
+        .map_err(ErrorInternalServerError)?;
+
+    let package = match kattis::import(&files, &languages) {
+        Ok(package) => package,
+        Err(error) => {
+            session.set("error", error)?;
+            return Ok(redirect());
+        }
+    };
+
+    let pid = create(&state.db, &user, &package)
+        .map_err(ErrorInternalServerError)?;
+
+    session.set("success", format!("{} was imported with {} tests.", package.name, package.tests.len()))?;
+    if !package.notes.is_empty() {
+        session.set("error", package.notes.join(" "))?;
+    }
+
+    Ok(HttpResponse::Found()
+        .header("location", format!("/admin/problems/{}/edit", pid))
+        .finish())
+}
+
+/// Saves an imported problem as a draft with its tests, tags and first
+/// revision.
+fn create(db: &SqliteConnection, user: &User, package: &Package) -> QueryResult<i32> {
+    let program = |interactive: bool| package.validator.as_ref()
+        .filter(|_| package.interactive == interactive);
+    let language = |interactive: bool| program(interactive).map(|&(lid, _)| lid);
+    let code = |interactive: bool| program(interactive).map(|&(_, ref code)| code.clone()).unwrap_or_default();
+
+    db.transaction(|| {
+        diesel::insert_into(problem::table)
+            .values((
+                problem::name.eq(&package.name),
+                problem::description.eq(&package.description),
+                problem::state.eq(ProblemState::Draft.code()),
+                problem::checker.eq(&package.checker),
+                problem::tolerance.eq(package.tolerance),
+                problem::checker_language.eq(language(false)),
+                problem::checker_code.eq(code(false)),
+                problem::interactive.eq(package.interactive),
+                problem::interactor_language.eq(language(true)),
+                problem::interactor_code.eq(code(true)),
+                problem::interactor_convention.eq(Convention::Kattis.mode()),
+                problem::time_limit.eq(package.time_limit),
+                problem::memory_limit.eq(package.memory_limit),
+                problem::output_limit.eq(package.output_limit),
+                problem::reference_language.eq(package.reference.as_ref().map(|&(lid, _)| lid)),
+                problem::reference_code.eq(package.reference.as_ref().map(|&(_, ref code)| code.clone()).unwrap_or_default()),
+            ))
+            .execute(db)?;
+
+        let pid = diesel::select(last_insert_rowid).get_result::<i32>(db)?;
+
+        for (i, &(ref input, ref output, sample)) in package.tests.iter().enumerate() {
+            diesel::insert_into(test_case::table)
+                .values((
+                    test_case::pid.eq(pid),
+                    test_case::position.eq(i as i32 + 1),
+                    test_case::input.eq(input),
+                    test_case::output.eq(output),
+                    test_case::sample.eq(sample),
+                ))
+                .execute(db)?;
+        }
+
+        tags::save(db, pid, &package.tags)?;
+        super::revisions::record(db, pid, user.id, &package.name, &package.description, None)?;
+
+        Ok(pid)
+    })
+}
@@ -0,0 +1,161 @@
+use actix_web::{
+    Result, Responder, State, Path, Scope,
+    error::{ ErrorInternalServerError, ErrorNotFound },
+};
+use diesel::prelude::*;
+use liquid::{ Object, Value };
+
+use ::{
+    AppState,
+    db::{
+        models::{ Language, Problem, Solution, User },
+        schema::{ language, problem, solution, user },
+    },
+    judge::{ SourceFile, Verdict, load_files },
+    middleware::Template,
+    plagiarism::Fingerprint,
+};
+
+/// Pairs less similar than this aren't reported.
+const THRESHOLD: f64 = 0.3;
+
+/// The most pairs shown in a report.
+const MAX_PAIRS: usize = 100;
+
+pub fn configure(scope: Scope<AppState>) -> Scope<AppState> {
+    scope.resource("/problem/{id}", |r| r.with(report))
+        .resource("/compare/{a}/{b}", |r| r.with(compare))
+}
+
+/// Ranks the pairs of accepted solutions to a problem by how similar they
+/// are. Solutions are only compared to solutions by other users in the same
+/// language.
+fn report((state, id): (State<AppState>, Path<i32>)) -> Result<impl Responder> {
+    let db = &state.db;
+
+    let problem = problem::table
+        .filter(problem::id.eq(*id))
+        .first::<Problem>(db)
+        .optional()
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorNotFound("No problem found."))?;
+
+    let solutions = solution::table
+        .inner_join(language::table)
+        .inner_join(user::table)
+        .filter(solution::pid.eq(problem.id))
+        .filter(solution::verdict.eq(Verdict::Accepted.code()))
+        .order(solution::id.asc())
+        .load::<(Solution, Language, User)>(db)
+        .map_err(ErrorInternalServerError)?;
+
+    let mut fingerprints = Vec::new();
+    for &(ref solution, ref language, _) in &solutions {
+        let files = load_files(db, solution, language).map_err(ErrorInternalServerError)?;
+        fingerprints.push(Fingerprint::new(&files));
+    }
+
+    let mut pairs = Vec::new();
+    for (i, &(ref a, _, _)) in solutions.iter().enumerate() {
+        for (j, &(ref b, _, _)) in solutions.iter().enumerate().skip(i + 1) {
+            if a.uid == b.uid || a.language != b.language {
+                continue;
+            }
+
+            let similarity = fingerprints[i].similarity(&fingerprints[j]);
+            if similarity >= THRESHOLD {
+                pairs.push((similarity, i, j));
+            }
+        }
+    }
+
+    pairs.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+    let count = pairs.len();
+
+    let pairs = pairs.into_iter()
+        .take(MAX_PAIRS)
+        .map(|(similarity, i, j)| {
+            let (ref a, ref language, ref a_author) = solutions[i];
+            let (ref b, _, ref b_author) = solutions[j];
+
+            let mut obj = Object::new();
+            obj.insert("similarity".into(), Value::scalar((similarity * 100.0).round() as i32));
+            obj.insert("a".into(), Value::scalar(a.id));
+            obj.insert("b".into(), Value::scalar(b.id));
+            obj.insert("a_author".into(), Value::scalar(a_author.sid.clone()));
+            obj.insert("b_author".into(), Value::scalar(b_author.sid.clone()));
+            obj.insert("language".into(), Value::scalar(language.name.clone()));
+            Value::Object(obj)
+        })
+        .collect::<Vec<_>>();
+
+    let mut obj = Object::new();
+    obj.insert("problem".into(), Value::Object(problem.to_liquid(true)));
+    obj.insert("solutions".into(), Value::scalar(solutions.len() as i32));
+    obj.insert("pairs".into(), Value::array(pairs));
+    obj.insert("count".into(), Value::scalar(count as i32));
+    obj.insert("threshold".into(), Value::scalar((THRESHOLD * 100.0) as i32));
+    Ok(Template::render("admin/plagiarism.liquid", obj))
+}
+
+/// Shows two solutions side by side with the code they share highlighted.
+fn compare((state, ids): (State<AppState>, Path<(i32, i32)>)) -> Result<impl Responder> {
+    let db = &state.db;
+    let (a, b) = *ids;
+
+    let load = |sid: i32| -> Result<(Solution, Language, User, Vec<SourceFile>)> {
+        let (solution, language, author) = solution::table
+            .inner_join(language::table)
+            .inner_join(user::table)
+            .filter(solution::id.eq(sid))
+            .first::<(Solution, Language, User)>(db)
+            .optional()
+            .map_err(ErrorInternalServerError)?
+            .ok_or_else(|| ErrorNotFound("No solution found."))?;
+
+        let files = load_files(db, &solution, &language).map_err(ErrorInternalServerError)?;
+        Ok((solution, language, author, files))
+    };
+
+    let a = load(a)?;
+    let b = load(b)?;
+
+    let a_print = Fingerprint::new(&a.3);
+    let b_print = Fingerprint::new(&b.3);
+    let similarity = a_print.similarity(&b_print);
+
+    let a_files = highlight(&a.3, &a_print, &b_print);
+    let b_files = highlight(&b.3, &b_print, &a_print);
+
+    let side = |solution: Solution, language: Language, author: User, files: Value| {
+        let mut obj = solution.to_liquid();
+        obj.remove("code");
+        obj.insert("language_name".into(), Value::scalar(language.name));
+        obj.insert("author".into(), Value::scalar(author.sid));
+        obj.insert("files".into(), files);
+        Value::Object(obj)
+    };
+
+    let mut obj = Object::new();
+    obj.insert("similarity".into(), Value::scalar((similarity * 100.0).round() as i32));
+    obj.insert("a".into(), side(a.0, a.1, a.2, a_files));
+    obj.insert("b".into(), side(b.0, b.1, b.2, b_files));
+    Ok(Template::render("admin/compare.liquid", obj))
+}
+
+/// Converts a solution's files to a list with the code shared with the other
+/// solution marked.
+fn highlight(files: &[SourceFile], print: &Fingerprint, other: &Fingerprint) -> Value {
+    let files = files.iter()
+        .zip(print.highlight(files, other))
+        .map(|(file, parts)| {
+            let parts = parts.iter().map(|part| Value::Object(part.to_liquid()));
+
+            let mut obj = Object::new();
+            obj.insert("name".into(), Value::scalar(file.name.clone()));
+            obj.insert("parts".into(), Value::array(parts));
+            Value::Object(obj)
+        });
+
+    Value::array(files)
+}
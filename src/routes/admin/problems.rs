@@ -1,13 +1,22 @@
 use actix_web::{
-    Result, Responder, State, Path, Scope, Form, HttpResponse,
-    error::{ ErrorInternalServerError, ErrorNotFound },
+    Result, Responder, State, Path, Scope, Form, HttpRequest, HttpResponse,
+    error::{ ErrorBadRequest, ErrorInternalServerError, ErrorNotFound },
+    middleware::session::Session,
 };
+use chrono::NaiveDateTime;
 use diesel::{ self, prelude::* };
 use liquid::{ Object, Value };
 use ::{
     AppState,
-    db::{ models::Problem, schema::problem, },
+    db::{
+        last_insert_rowid,
+        models::{ DATETIME_INPUT, Language, Problem, ProblemState, User },
+        schema::{ language, problem, problem_language, problem_revision, problem_tag, reference_run, test_case },
+    },
+    judge::reference::{ self, Reference },
     middleware::Template,
+    routes::problems::difficulties,
+    tags,
 };
 
 pub fn configure(scope: Scope<AppState>) -> Scope<AppState> {
@@ -15,30 +24,156 @@ pub fn configure(scope: Scope<AppState>) -> Scope<AppState> {
             r.get().with(create);
             r.post().with(create_form);
         })
+        .resource("/import", |r| {
+            r.get().with(super::packages::import);
+            r.post().with_async(super::packages::import_form);
+        })
         .resource("/{id}/edit", |r| {
             r.get().with(edit);
             r.post().with(edit_form);
         })
+        .resource("/{id}/languages/{lid}", |r| r.post().with(multiplier_form))
+        .resource("/{id}/preview", |r| r.with(preview))
+        .resource("/{id}/publish", |r| r.with(publish))
+        .resource("/{id}/unpublish", |r| r.with(unpublish))
+        .resource("/{id}/archive", |r| r.with(archive))
+        .resource("/{id}/export", |r| r.with(super::packages::export))
         .resource("/{id}/delete", |r| r.with(delete))
         .resource("/{id}/delete/confirm", |r| r.with(delete_confirm))
+        .nested("/{id}/tests", |s| super::tests::configure(s))
+        .nested("/{id}/revisions", |s| super::revisions::configure(s))
 }
 
 #[derive(Debug, Deserialize)]
 struct ProblemForm {
     name: String,
     description: String,
+    /// Left empty to release the problem right away.
+    release_at: String,
+    /// Left empty for problems without a deadline.
+    due_at: String,
+    /// The submission limits are left empty for no limit.
+    max_attempts: String,
+    daily_attempts: String,
+    cooldown: String,
+    /// Milliseconds of CPU time each test can use.
+    time_limit: String,
+    /// Megabytes of memory and output each test can use.
+    memory_limit: String,
+    output_limit: String,
+    /// Comma separated, such as "graphs, dp".
+    tags: String,
+    /// Left empty for problems that haven't been rated.
+    difficulty: String,
+}
+
+impl ProblemForm {
+    /// Gets when the problem is released and when it's due.
+    fn dates(&self) -> Result<(Option<NaiveDateTime>, Option<NaiveDateTime>)> {
+        let date = |date: &str, error: &'static str| match date.trim() {
+            "" => Ok(None),
+            date => NaiveDateTime::parse_from_str(date, DATETIME_INPUT)
+                .map(Some)
+                .map_err(|_| ErrorBadRequest(error)),
+        };
+
+        let release_at = date(&self.release_at, "Invalid release date.")?;
+        let due_at = date(&self.due_at, "Invalid due date.")?;
+
+        if let (Some(release_at), Some(due_at)) = (release_at, due_at) {
+            if due_at <= release_at {
+                return Err(ErrorBadRequest("A problem has to be due after it's released."));
+            }
+        }
+
+        Ok((release_at, due_at))
+    }
+
+    /// Gets the submission limits, the number of attempts, attempts each
+    /// day and the cooldown.
+    fn limits(&self) -> Result<(Option<i32>, Option<i32>, Option<i32>)> {
+        let limit = |limit: &str| match limit.trim() {
+            "" => Ok(None),
+            limit => limit.parse::<i32>()
+                .ok()
+                .filter(|&limit| limit >= 0)
+                .map(Some)
+                .ok_or_else(|| ErrorBadRequest("Limits have to be positive numbers.")),
+        };
+
+        Ok((limit(&self.max_attempts)?, limit(&self.daily_attempts)?, limit(&self.cooldown)?))
+    }
+
+    /// Gets the resource limits, time in milliseconds and memory and output
+    /// in megabytes.
+    fn resources(&self) -> Result<(i32, i32, i32)> {
+        let limit = |limit: &str| limit.trim()
+            .parse::<i32>()
+            .ok()
+            .filter(|&limit| limit > 0)
+            .ok_or_else(|| ErrorBadRequest("Resource limits have to be positive numbers."));
+
+        Ok((limit(&self.time_limit)?, limit(&self.memory_limit)?, limit(&self.output_limit)?))
+    }
+
+    /// Gets the problem's tags and difficulty.
+    fn topic(&self) -> Result<(Vec<String>, Option<i32>)> {
+        let tags = tags::parse(&self.tags).map_err(ErrorBadRequest)?;
+
+        let difficulty = match self.difficulty.trim() {
+            "" => None,
+            difficulty => Some(difficulty.parse::<i32>()
+                .ok()
+                .filter(|&difficulty| Problem::DIFFICULTIES.iter().any(|&(level, _)| level == difficulty))
+                .ok_or_else(|| ErrorBadRequest("Invalid difficulty."))?),
+        };
+
+        Ok((tags, difficulty))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct MultiplierForm {
+    /// Left empty to give the language the problem's time limit as it is.
+    time_multiplier: String,
 }
 
 fn create(_: State<AppState>) -> impl Responder {
-    Template::render("admin/problem.liquid", None)
+    let mut obj = Object::new();
+    obj.insert("difficulties".into(), difficulties());
+    Template::render("admin/problem.liquid", obj)
 }
 
-fn create_form((state, form): (State<AppState>, Form<ProblemForm>)) -> Result<impl Responder> {
-    let ProblemForm { name, description } = form.into_inner();
+fn create_form((state, user, form): (State<AppState>, User, Form<ProblemForm>)) -> Result<impl Responder> {
+    let (release_at, due_at) = form.dates()?;
+    let (max_attempts, daily_attempts, cooldown) = form.limits()?;
+    let (time_limit, memory_limit, output_limit) = form.resources()?;
+    let (tags, difficulty) = form.topic()?;
+    let ProblemForm { name, description, .. } = form.into_inner();
+    let db = &state.db;
+
+    db.transaction::<_, diesel::result::Error, _>(|| {
+            diesel::insert_into(problem::table)
+                .values((
+                    problem::name.eq(&name),
+                    problem::description.eq(&description),
+                    problem::state.eq(ProblemState::Draft.code()),
+                    problem::release_at.eq(release_at),
+                    problem::due_at.eq(due_at),
+                    problem::max_attempts.eq(max_attempts),
+                    problem::daily_attempts.eq(daily_attempts),
+                    problem::cooldown.eq(cooldown),
+                    problem::time_limit.eq(time_limit),
+                    problem::memory_limit.eq(memory_limit),
+                    problem::output_limit.eq(output_limit),
+                    problem::difficulty.eq(difficulty),
+                ))
+                .execute(db)?;
 
-    diesel::insert_into(problem::table)
-        .values((problem::name.eq(name), problem::description.eq(description)))
-        .execute(&state.db)
+            let pid = diesel::select(last_insert_rowid).get_result::<i32>(db)?;
+            tags::save(db, pid, &tags)?;
+            super::revisions::record(db, pid, user.id, &name, &description, None)
+        })
         .map_err(ErrorInternalServerError)?;
 
     Ok(HttpResponse::Found().header("location", "/admin/").finish())
@@ -52,22 +187,192 @@ fn edit((state, id): (State<AppState>, Path<i32>)) -> Result<impl Responder> {
         .map_err(ErrorInternalServerError)?
         .ok_or_else(|| ErrorNotFound("No problem found."))?;
 
+    let multipliers = problem_language::table
+        .filter(problem_language::pid.eq(problem.id))
+        .select((problem_language::lid, problem_language::time_multiplier))
+        .load::<(i32, f64)>(&state.db)
+        .map_err(ErrorInternalServerError)?;
+
+    let tags = tags::for_problem(&state.db, problem.id)
+        .map_err(ErrorInternalServerError)?;
+
+    let languages = language::table
+        .load::<Language>(&state.db)
+        .map_err(ErrorInternalServerError)?
+        .into_iter()
+        .map(|lang| {
+            let multiplier = multipliers.iter()
+                .find(|&&(lid, _)| lid == lang.id)
+                .map(|&(_, multiplier)| multiplier.to_string())
+                .unwrap_or_default();
+
+            let mut obj = lang.to_liquid();
+            obj.insert("time_multiplier".into(), Value::scalar(multiplier));
+            Value::Object(obj)
+        });
+
     let mut obj = Object::new();
     obj.insert("problem".into(), Value::Object(problem.to_liquid(false)));
+    obj.insert("languages".into(), Value::array(languages));
+    obj.insert("tags".into(), Value::scalar(tags.join(", ")));
+    obj.insert("difficulties".into(), difficulties());
     Ok(Template::render("admin/problem.liquid", obj))
 }
 
-fn edit_form((state, id, form): (State<AppState>, Path<i32>, Form<ProblemForm>)) -> Result<impl Responder> {
-    let ProblemForm { name, description } = form.into_inner();
+/// Saves the problem, keeping a revision if its name or description changed.
+fn edit_form((state, user, id, form): (State<AppState>, User, Path<i32>, Form<ProblemForm>)) -> Result<impl Responder> {
+    let (release_at, due_at) = form.dates()?;
+    let (max_attempts, daily_attempts, cooldown) = form.limits()?;
+    let (time_limit, memory_limit, output_limit) = form.resources()?;
+    let (tags, difficulty) = form.topic()?;
+    let ProblemForm { name, description, .. } = form.into_inner();
+    let db = &state.db;
+    let problem = get_problem(db, *id)?;
+
+    db.transaction::<_, diesel::result::Error, _>(|| {
+            diesel::update(problem::table.filter(problem::id.eq(problem.id)))
+                .set((
+                    problem::name.eq(&name),
+                    problem::description.eq(&description),
+                    problem::release_at.eq(release_at),
+                    problem::due_at.eq(due_at),
+                    problem::max_attempts.eq(max_attempts),
+                    problem::daily_attempts.eq(daily_attempts),
+                    problem::cooldown.eq(cooldown),
+                    problem::time_limit.eq(time_limit),
+                    problem::memory_limit.eq(memory_limit),
+                    problem::output_limit.eq(output_limit),
+                    problem::difficulty.eq(difficulty),
+                ))
+                .execute(db)?;
 
-    diesel::update(problem::table.filter(problem::id.eq(*id)))
-        .set((problem::name.eq(name), problem::description.eq(description)))
-        .execute(&state.db)
+            tags::save(db, problem.id, &tags)?;
+
+            if name != problem.name || description != problem.description {
+                super::revisions::record(db, problem.id, user.id, &name, &description, None)?;
+            }
+
+            Ok(())
+        })
         .map_err(ErrorInternalServerError)?;
 
     Ok(HttpResponse::Found().header("location", "/admin/").finish())
 }
 
+fn multiplier_form((state, ids, form): (State<AppState>, Path<(i32, i32)>, Form<MultiplierForm>)) -> Result<impl Responder> {
+    let (pid, lid) = *ids;
+
+    let multiplier = match form.time_multiplier.trim() {
+        "" => None,
+        multiplier => Some(multiplier.parse::<f64>()
+            .ok()
+            .filter(|&multiplier| multiplier > 0.0 && multiplier.is_finite())
+            .ok_or_else(|| ErrorBadRequest("Time multipliers have to be positive numbers."))?),
+    };
+
+    let rows = problem_language::table
+        .filter(problem_language::pid.eq(pid))
+        .filter(problem_language::lid.eq(lid));
+
+    let result = match multiplier {
+        Some(multiplier) => diesel::replace_into(problem_language::table)
+            .values((
+                problem_language::pid.eq(pid),
+                problem_language::lid.eq(lid),
+                problem_language::time_multiplier.eq(multiplier),
+            ))
+            .execute(&state.db),
+        None => diesel::delete(rows).execute(&state.db),
+    };
+
+    result.map_err(ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Found().header("location", format!("/admin/problems/{}/edit", pid)).finish())
+}
+
+fn get_problem(db: &SqliteConnection, pid: i32) -> Result<Problem> {
+    problem::table
+        .filter(problem::id.eq(pid))
+        .first::<Problem>(db)
+        .optional()
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorNotFound("No problem found."))
+}
+
+fn redirect(pid: i32) -> HttpResponse {
+    HttpResponse::Found()
+        .header("location", format!("/admin/problems/{}/edit", pid))
+        .finish()
+}
+
+fn set_state(db: &SqliteConnection, pid: i32, state: ProblemState) -> Result<()> {
+    diesel::update(problem::table.filter(problem::id.eq(pid)))
+        .set(problem::state.eq(state.code()))
+        .execute(db)
+        .map_err(ErrorInternalServerError)?;
+
+    Ok(())
+}
+
+/// Shows a problem the way students will see it, whatever state it's in.
+fn preview((req, id): (HttpRequest<AppState>, Path<i32>)) -> Result<impl Responder> {
+    let state = req.state();
+    let current_user = req.extensions().get::<User>().cloned();
+    let problem = get_problem(&state.db, *id)?;
+
+    super::super::problems::page(state, current_user, problem, true)
+}
+
+/// Publishes a problem if it's ready, otherwise the admin is told what's
+/// missing. The reference solution is judged in the background the first
+/// time, so the admin has to publish again once it's done.
+fn publish((state, session, id): (State<AppState>, Session, Path<i32>)) -> Result<impl Responder> {
+    let problem = get_problem(&state.db, *id)?;
+
+    match check_publishable(&state, &problem)? {
+        Reference::Accepted => {
+            set_state(&state.db, problem.id, ProblemState::Published)?;
+            session.set("success", format!("{} was published.", problem.name))?;
+        }
+        Reference::Rejected(error) => session.set("error", error)?,
+        Reference::Judging => session.set("error", "The reference solution is being judged, publish the problem again in a moment.")?,
+    }
+
+    Ok(redirect(problem.id))
+}
+
+/// Makes sure a problem has tests and that its reference solution is
+/// accepted by them.
+fn check_publishable(state: &AppState, problem: &Problem) -> Result<Reference> {
+    let tests = test_case::table
+        .filter(test_case::pid.eq(problem.id))
+        .count()
+        .get_result::<i64>(&state.db)
+        .map_err(ErrorInternalServerError)?;
+
+    if tests == 0 {
+        return Ok(Reference::Rejected("A problem needs tests before it can be published.".into()));
+    }
+
+    reference::check(&state.db, &state.judge, problem)
+        .map_err(ErrorInternalServerError)
+}
+
+/// Hides a problem from students again.
+fn unpublish((state, session, id): (State<AppState>, Session, Path<i32>)) -> Result<impl Responder> {
+    let problem = get_problem(&state.db, *id)?;
+    set_state(&state.db, problem.id, ProblemState::Draft)?;
+    session.set("success", format!("{} is a draft again.", problem.name))?;
+    Ok(redirect(problem.id))
+}
+
+/// Stops a problem from accepting solutions, students can still read it.
+fn archive((state, session, id): (State<AppState>, Session, Path<i32>)) -> Result<impl Responder> {
+    let problem = get_problem(&state.db, *id)?;
+    set_state(&state.db, problem.id, ProblemState::Archived)?;
+    session.set("success", format!("{} was archived.", problem.name))?;
+    Ok(redirect(problem.id))
+}
 
 fn delete((state, id): (State<AppState>, Path<i32>)) -> Result<impl Responder> {
     let problem = problem::table
@@ -84,8 +389,30 @@ fn delete((state, id): (State<AppState>, Path<i32>)) -> Result<impl Responder> {
 }
 
 fn delete_confirm((state, id): (State<AppState>, Path<i32>)) -> Result<impl Responder> {
-    diesel::delete(problem::table.filter(problem::id.eq(*id)))
-        .execute(&state.db)
+    let db = &state.db;
+
+    db.transaction::<_, diesel::result::Error, _>(|| {
+            diesel::delete(test_case::table.filter(test_case::pid.eq(*id)))
+                .execute(db)?;
+
+            diesel::delete(problem_language::table.filter(problem_language::pid.eq(*id)))
+                .execute(db)?;
+
+            diesel::delete(problem_revision::table.filter(problem_revision::pid.eq(*id)))
+                .execute(db)?;
+
+            diesel::delete(problem_tag::table.filter(problem_tag::pid.eq(*id)))
+                .execute(db)?;
+            tags::prune(db)?;
+
+            diesel::delete(reference_run::table.filter(reference_run::pid.eq(*id)))
+                .execute(db)?;
+
+            diesel::delete(problem::table.filter(problem::id.eq(*id)))
+                .execute(db)?;
+
+            Ok(())
+        })
         .map_err(ErrorInternalServerError)?;
 
     Ok(HttpResponse::Found().header("location", "/admin/").finish())
@@ -0,0 +1,212 @@
+use actix_web::{
+    Result, Responder, State, Path, Scope, HttpResponse,
+    error::{ ErrorInternalServerError, ErrorNotFound },
+};
+use diesel::prelude::*;
+use liquid::{ Object, Value };
+
+use ::{
+    AppState,
+    db::{
+        models::{ Language, Problem, Rejudge, RejudgeChange, Solution },
+        schema::{ language, problem, rejudge, rejudge_change, solution, user },
+    },
+    judge::queue,
+    middleware::Template,
+};
+
+pub fn configure(scope: Scope<AppState>) -> Scope<AppState> {
+    scope.resource("/", |r| r.with(index))
+        .resource("/{id}/", |r| r.with(progress))
+        .resource("/solution/{id}", |r| r.with(rejudge_solution))
+        .resource("/solution/{id}/confirm", |r| r.with(rejudge_solution_confirm))
+        .resource("/problem/{id}", |r| r.with(rejudge_problem))
+        .resource("/problem/{id}/confirm", |r| r.with(rejudge_problem_confirm))
+        .resource("/language/{id}", |r| r.with(rejudge_language))
+        .resource("/language/{id}/confirm", |r| r.with(rejudge_language_confirm))
+}
+
+fn index(state: State<AppState>) -> Result<impl Responder> {
+    let db = &state.db;
+
+    let rejudges = rejudge::table
+        .order(rejudge::id.desc())
+        .load::<Rejudge>(db)
+        .map_err(ErrorInternalServerError)?;
+
+    let mut list = Vec::new();
+    for rejudge in rejudges {
+        let changes = rejudge_change::table
+            .filter(rejudge_change::rid.eq(rejudge.id))
+            .load::<RejudgeChange>(db)
+            .map_err(ErrorInternalServerError)?;
+
+        let mut obj = rejudge.to_liquid();
+        insert_progress(&mut obj, &changes);
+        list.push(Value::Object(obj));
+    }
+
+    let mut obj = Object::new();
+    obj.insert("rejudges".into(), Value::array(list));
+    Ok(Template::render("admin/rejudges.liquid", obj))
+}
+
+fn progress((state, id): (State<AppState>, Path<i32>)) -> Result<impl Responder> {
+    let db = &state.db;
+
+    let rejudge = rejudge::table
+        .filter(rejudge::id.eq(*id))
+        .first::<Rejudge>(db)
+        .optional()
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorNotFound("No rejudge found."))?;
+
+    let changes = rejudge_change::table
+        .filter(rejudge_change::rid.eq(rejudge.id))
+        .inner_join(solution::table.inner_join(user::table).inner_join(problem::table))
+        .select((rejudge_change::all_columns, user::sid, problem::name))
+        .order(rejudge_change::id.asc())
+        .load::<(RejudgeChange, String, String)>(db)
+        .map_err(ErrorInternalServerError)?;
+
+    let mut obj = rejudge.to_liquid();
+    insert_progress(&mut obj, changes.iter().map(|&(ref change, _, _)| change));
+
+    let changes = changes.into_iter()
+        .map(|(change, user, name)| {
+            let mut obj = change.to_liquid();
+            obj.insert("user".into(), Value::scalar(user));
+            obj.insert("name".into(), Value::scalar(name));
+            Value::Object(obj)
+        });
+    obj.insert("changes".into(), Value::array(changes));
+
+    let mut page = Object::new();
+    page.insert("rejudge".into(), Value::Object(obj));
+    Ok(Template::render("admin/rejudge.liquid", page))
+}
+
+/// Adds how many of a rejudge's solutions have been judged and how many
+/// verdicts changed.
+fn insert_progress<'a, I>(obj: &mut Object, changes: I)
+    where I: IntoIterator<Item = &'a RejudgeChange>
+{
+    let (mut total, mut judged, mut changed) = (0, 0, 0);
+    for change in changes {
+        total += 1;
+        if let Some(ref verdict) = change.new_verdict {
+            judged += 1;
+            if *verdict != change.old_verdict || change.new_score != Some(change.old_score) {
+                changed += 1;
+            }
+        }
+    }
+
+    obj.insert("total".into(), Value::scalar(total));
+    obj.insert("judged".into(), Value::scalar(judged));
+    obj.insert("changed".into(), Value::scalar(changed));
+    obj.insert("done".into(), Value::scalar(judged == total));
+}
+
+fn rejudge_solution((state, id): (State<AppState>, Path<i32>)) -> Result<impl Responder> {
+    let solution = get_solution(&state.db, *id)?;
+
+    Ok(confirm(
+        format!("Are you sure you want to rejudge solution {}?", solution.id),
+        format!("/admin/rejudges/solution/{}/confirm", solution.id),
+    ))
+}
+
+fn rejudge_solution_confirm((state, id): (State<AppState>, Path<i32>)) -> Result<impl Responder> {
+    let solution = get_solution(&state.db, *id)?;
+
+    start(&state.db, &format!("Solution {}", solution.id), &[solution])
+}
+
+fn rejudge_problem((state, id): (State<AppState>, Path<i32>)) -> Result<impl Responder> {
+    let problem = get_problem(&state.db, *id)?;
+
+    Ok(confirm(
+        format!("Are you sure you want to rejudge every solution to {}?", problem.name),
+        format!("/admin/rejudges/problem/{}/confirm", problem.id),
+    ))
+}
+
+fn rejudge_problem_confirm((state, id): (State<AppState>, Path<i32>)) -> Result<impl Responder> {
+    let db = &state.db;
+    let problem = get_problem(db, *id)?;
+
+    let solutions = solution::table
+        .filter(solution::pid.eq(problem.id))
+        .order(solution::id.asc())
+        .load::<Solution>(db)
+        .map_err(ErrorInternalServerError)?;
+
+    start(db, &format!("Problem {}", problem.name), &solutions)
+}
+
+fn rejudge_language((state, id): (State<AppState>, Path<i32>)) -> Result<impl Responder> {
+    let language = get_language(&state.db, *id)?;
+
+    Ok(confirm(
+        format!("Are you sure you want to rejudge every solution in {}?", language.name),
+        format!("/admin/rejudges/language/{}/confirm", language.id),
+    ))
+}
+
+fn rejudge_language_confirm((state, id): (State<AppState>, Path<i32>)) -> Result<impl Responder> {
+    let db = &state.db;
+    let language = get_language(db, *id)?;
+
+    let solutions = solution::table
+        .filter(solution::language.eq(language.id))
+        .order(solution::id.asc())
+        .load::<Solution>(db)
+        .map_err(ErrorInternalServerError)?;
+
+    start(db, &format!("Language {}", language.name), &solutions)
+}
+
+fn confirm(confirmation: String, url: String) -> Template {
+    let mut obj = Object::new();
+    obj.insert("confirmation".into(), Value::scalar(confirmation));
+    obj.insert("url".into(), Value::scalar(url));
+    Template::render("confirm.liquid", obj)
+}
+
+/// Queues the solutions and shows the progress of judging them.
+fn start(db: &SqliteConnection, description: &str, solutions: &[Solution]) -> Result<HttpResponse> {
+    let rid = queue::rejudge(db, description, solutions)
+        .map_err(ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Found()
+        .header("location", format!("/admin/rejudges/{}/", rid))
+        .finish())
+}
+
+fn get_solution(db: &SqliteConnection, sid: i32) -> Result<Solution> {
+    solution::table
+        .filter(solution::id.eq(sid))
+        .first::<Solution>(db)
+        .optional()
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorNotFound("No solution found."))
+}
+
+fn get_problem(db: &SqliteConnection, pid: i32) -> Result<Problem> {
+    problem::table
+        .filter(problem::id.eq(pid))
+        .first::<Problem>(db)
+        .optional()
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorNotFound("No problem found."))
+}
+
+fn get_language(db: &SqliteConnection, lid: i32) -> Result<Language> {
+    language::table
+        .filter(language::id.eq(lid))
+        .first::<Language>(db)
+        .optional()
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorNotFound("No language found."))
+}
@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+
+use actix_web::{
+    Result, Responder, State, Path, Query, Scope, HttpResponse,
+    error::{ ErrorInternalServerError, ErrorNotFound },
+    middleware::session::Session,
+};
+use chrono::Utc;
+use diesel::{ self, prelude::* };
+use liquid::{ Object, Value };
+
+use ::{
+    AppState,
+    db::{
+        models::{ Problem, ProblemRevision, User },
+        schema::{ problem, problem_revision, user },
+    },
+    diff,
+    middleware::Template,
+};
+
+pub fn configure(scope: Scope<AppState>) -> Scope<AppState> {
+    scope.resource("", |r| r.with(index))
+        .resource("/compare", |r| r.with(compare))
+        .resource("/{rid}/restore", |r| r.with(restore))
+}
+
+/// Saves the name and description a problem was given by an edit.
+pub fn record(db: &SqliteConnection, pid: i32, uid: i32, name: &str, description: &str, restored_from: Option<i32>) -> QueryResult<()> {
+    diesel::insert_into(problem_revision::table)
+        .values((
+            problem_revision::pid.eq(pid),
+            problem_revision::uid.eq(uid),
+            problem_revision::name.eq(name),
+            problem_revision::description.eq(description),
+            problem_revision::created_at.eq(Utc::now().naive_utc()),
+            problem_revision::restored_from.eq(restored_from),
+        ))
+        .execute(db)?;
+
+    Ok(())
+}
+
+fn get_problem(db: &SqliteConnection, pid: i32) -> Result<Problem> {
+    problem::table
+        .filter(problem::id.eq(pid))
+        .first::<Problem>(db)
+        .optional()
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorNotFound("No problem found."))
+}
+
+/// Loads a problem's revisions, oldest first, with the admins who made them.
+fn load_revisions(db: &SqliteConnection, pid: i32) -> Result<Vec<(ProblemRevision, Option<User>)>> {
+    problem_revision::table
+        .left_join(user::table)
+        .filter(problem_revision::pid.eq(pid))
+        .order(problem_revision::id.asc())
+        .load::<(ProblemRevision, Option<User>)>(db)
+        .map_err(ErrorInternalServerError)
+}
+
+fn redirect(pid: i32) -> HttpResponse {
+    HttpResponse::Found()
+        .header("location", format!("/admin/problems/{}/revisions", pid))
+        .finish()
+}
+
+/// Lists a problem's revisions, newest first. Revisions are numbered from
+/// the oldest one.
+fn index((state, pid): (State<AppState>, Path<i32>)) -> Result<impl Responder> {
+    let problem = get_problem(&state.db, *pid)?;
+    let revisions = load_revisions(&state.db, problem.id)?;
+
+    let numbers = revisions.iter()
+        .enumerate()
+        .map(|(i, &(ref revision, _))| (revision.id, i as i32 + 1))
+        .collect::<HashMap<_, _>>();
+
+    let mut previous = None;
+    let mut items = Vec::new();
+    for (revision, author) in revisions {
+        let id = revision.id;
+        let restored_from = revision.restored_from.and_then(|rid| numbers.get(&rid).cloned());
+
+        let mut obj = revision.to_liquid();
+        obj.remove("description");
+        obj.insert("number".into(), Value::scalar(numbers[&id]));
+        obj.insert("author".into(), Value::scalar(author.map(|author| author.sid).unwrap_or_default()));
+        if let Some(previous) = previous {
+            obj.insert("previous".into(), Value::scalar(previous));
+        }
+        if let Some(restored_from) = restored_from {
+            obj.insert("restored_number".into(), Value::scalar(restored_from));
+        }
+        items.push(Value::Object(obj));
+        previous = Some(id);
+    }
+
+    // The newest revision is how the problem is now.
+    items.reverse();
+    let current = items.first().cloned();
+
+    let mut obj = Object::new();
+    obj.insert("problem".into(), Value::Object(problem.to_liquid(false)));
+    obj.insert("revisions".into(), Value::array(items));
+    if let Some(current) = current {
+        obj.insert("current".into(), current);
+    }
+    Ok(Template::render("admin/revisions.liquid", obj))
+}
+
+#[derive(Deserialize)]
+struct CompareQuery {
+    /// Left out to compare against nothing, for the first revision.
+    from: Option<i32>,
+    to: i32,
+}
+
+/// Shows how the name and description changed between two revisions.
+fn compare((state, pid, query): (State<AppState>, Path<i32>, Query<CompareQuery>)) -> Result<impl Responder> {
+    let problem = get_problem(&state.db, *pid)?;
+    let revisions = load_revisions(&state.db, problem.id)?;
+
+    let find = |rid: i32| revisions.iter()
+        .enumerate()
+        .find(|&(_, &(ref revision, _))| revision.id == rid)
+        .map(|(i, &(ref revision, ref author))| (i as i32 + 1, revision, author))
+        .ok_or_else(|| ErrorNotFound("No revision found."));
+
+    let to = find(query.to)?;
+    let from = match query.from {
+        Some(rid) => Some(find(rid)?),
+        None => None,
+    };
+
+    let (old_name, old_description) = from
+        .map(|(_, revision, _)| (&revision.name[..], &revision.description[..]))
+        .unwrap_or(("", ""));
+
+    let side = |(number, revision, author): (i32, &ProblemRevision, &Option<User>)| {
+        let mut obj = revision.clone().to_liquid();
+        obj.remove("description");
+        obj.insert("number".into(), Value::scalar(number));
+        obj.insert("author".into(), Value::scalar(author.as_ref().map(|author| author.sid.clone()).unwrap_or_default()));
+        Value::Object(obj)
+    };
+
+    let mut obj = Object::new();
+    obj.insert("problem".into(), Value::Object(problem.to_liquid(false)));
+    obj.insert("name".into(), diff::to_liquid(&diff::lines(old_name, &to.1.name)));
+    obj.insert("description".into(), diff::to_liquid(&diff::lines(old_description, &to.1.description)));
+    if let Some(from) = from {
+        obj.insert("from".into(), side(from));
+    }
+    obj.insert("to".into(), side(to));
+    Ok(Template::render("admin/revision.liquid", obj))
+}
+
+/// Puts an older revision's name and description back. Restoring is saved
+/// as a new revision so it can be undone too.
+fn restore((state, session, user, ids): (State<AppState>, Session, User, Path<(i32, i32)>)) -> Result<impl Responder> {
+    let db = &state.db;
+    let (pid, rid) = *ids;
+    let problem = get_problem(db, pid)?;
+
+    let revision = problem_revision::table
+        .filter(problem_revision::id.eq(rid))
+        .filter(problem_revision::pid.eq(problem.id))
+        .first::<ProblemRevision>(db)
+        .optional()
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorNotFound("No revision found."))?;
+
+    if revision.name == problem.name && revision.description == problem.description {
+        session.set("error", "The problem already looks like that revision.")?;
+        return Ok(redirect(problem.id));
+    }
+
+    db.transaction::<_, diesel::result::Error, _>(|| {
+            diesel::update(problem::table.filter(problem::id.eq(problem.id)))
+                .set((
+                    problem::name.eq(&revision.name),
+                    problem::description.eq(&revision.description),
+                ))
+                .execute(db)?;
+
+            record(db, problem.id, user.id, &revision.name, &revision.description, Some(revision.id))
+        })
+        .map_err(ErrorInternalServerError)?;
+
+    session.set("success", "The revision was restored.")?;
+    Ok(redirect(problem.id))
+}
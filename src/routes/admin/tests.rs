@@ -0,0 +1,459 @@
+use std::collections::{ BTreeMap, HashMap };
+
+use actix_web::{
+    Result, Error, Responder, State, Path, Scope, Form, HttpRequest, HttpResponse,
+    error::{ ErrorInternalServerError, ErrorNotFound },
+    middleware::session::RequestSession,
+};
+use diesel::{ self, prelude::*, dsl::max };
+use futures::Future;
+use liquid::{ Object, Value };
+
+use ::{
+    AppState,
+    db::{
+        models::{ Language, Problem, TestCase },
+        schema::{ language, problem, test_case },
+    },
+    judge::{ Checker, Convention },
+    middleware::Template,
+    routes::multipart,
+};
+
+/// The largest upload of test files we'll accept.
+const UPLOAD_LIMIT: usize = 64 * 1024 * 1024;
+
+pub fn configure(scope: Scope<AppState>) -> Scope<AppState> {
+    scope.resource("", |r| r.with(index))
+        .resource("/create", |r| {
+            r.get().with(create);
+            r.post().with(create_form);
+        })
+        .resource("/upload", |r| r.post().with_async(upload))
+        .resource("/checker", |r| r.post().with_async(checker))
+        .resource("/interactor", |r| r.post().with_async(interactor))
+        .resource("/reference", |r| r.post().with_async(reference))
+        .resource("/{tid}/edit", |r| {
+            r.get().with(edit);
+            r.post().with(edit_form);
+        })
+        .resource("/{tid}/up", |r| r.with(move_up))
+        .resource("/{tid}/down", |r| r.with(move_down))
+        .resource("/{tid}/delete", |r| r.with(delete))
+        .resource("/{tid}/delete/confirm", |r| r.with(delete_confirm))
+}
+
+#[derive(Deserialize)]
+struct TestForm {
+    input: String,
+    output: String,
+    sample: Option<String>,
+}
+
+fn redirect(pid: i32) -> HttpResponse {
+    HttpResponse::Found()
+        .header("location", format!("/admin/problems/{}/tests", pid))
+        .finish()
+}
+
+/// Browsers send form data with windows line endings.
+fn normalize(text: &str) -> String {
+    text.replace("\r\n", "\n")
+}
+
+fn get_problem(db: &SqliteConnection, pid: i32) -> Result<Problem> {
+    problem::table
+        .filter(problem::id.eq(pid))
+        .first::<Problem>(db)
+        .optional()
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorNotFound("No problem found."))
+}
+
+fn get_test(db: &SqliteConnection, pid: i32, tid: i32) -> Result<TestCase> {
+    test_case::table
+        .filter(test_case::id.eq(tid))
+        .filter(test_case::pid.eq(pid))
+        .first::<TestCase>(db)
+        .optional()
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorNotFound("No test found."))
+}
+
+/// Adds tests to the end of a problem's tests.
+fn append(db: &SqliteConnection, pid: i32, tests: Vec<(String, String, bool)>) -> QueryResult<()> {
+    db.transaction(|| {
+        let last = test_case::table
+            .select(max(test_case::position))
+            .filter(test_case::pid.eq(pid))
+            .first::<Option<i32>>(db)?
+            .unwrap_or(0);
+
+        for (i, (input, output, sample)) in tests.into_iter().enumerate() {
+            diesel::insert_into(test_case::table)
+                .values((
+                    test_case::pid.eq(pid),
+                    test_case::position.eq(last + i as i32 + 1),
+                    test_case::input.eq(input),
+                    test_case::output.eq(output),
+                    test_case::sample.eq(sample),
+                ))
+                .execute(db)?;
+        }
+
+        Ok(())
+    })
+}
+
+fn index((state, pid): (State<AppState>, Path<i32>)) -> Result<impl Responder> {
+    let problem = get_problem(&state.db, *pid)?;
+
+    let tests = test_case::table
+        .filter(test_case::pid.eq(problem.id))
+        .order(test_case::position.asc())
+        .load::<TestCase>(&state.db)
+        .map_err(ErrorInternalServerError)?
+        .into_iter()
+        .map(|test| Value::Object(test.to_liquid()));
+
+    let languages = language::table
+        .filter(language::enabled.eq(true))
+        .load::<Language>(&state.db)
+        .map_err(ErrorInternalServerError)?
+        .into_iter()
+        .map(|lang| Value::Object(lang.to_liquid()));
+
+    let modes = |modes: &'static [(&'static str, &'static str)]| modes.iter()
+        .map(|&(mode, description)| {
+            let mut obj = Object::new();
+            obj.insert("mode".into(), Value::scalar(mode));
+            obj.insert("description".into(), Value::scalar(description));
+            Value::Object(obj)
+        });
+
+    let mut obj = Object::new();
+    obj.insert("problem".into(), Value::Object(problem.to_liquid(false)));
+    obj.insert("tests".into(), Value::array(tests));
+    obj.insert("languages".into(), Value::array(languages));
+    obj.insert("checkers".into(), Value::array(modes(Checker::MODES)));
+    obj.insert("conventions".into(), Value::array(modes(Convention::MODES)));
+    Ok(Template::render("admin/tests.liquid", obj))
+}
+
+fn create((state, pid): (State<AppState>, Path<i32>)) -> Result<impl Responder> {
+    let problem = get_problem(&state.db, *pid)?;
+
+    let mut obj = Object::new();
+    obj.insert("problem".into(), Value::Object(problem.to_liquid(false)));
+    Ok(Template::render("admin/test.liquid", obj))
+}
+
+fn create_form((state, pid, form): (State<AppState>, Path<i32>, Form<TestForm>)) -> Result<impl Responder> {
+    let problem = get_problem(&state.db, *pid)?;
+    let TestForm { input, output, sample } = form.into_inner();
+
+    append(&state.db, problem.id, vec![(normalize(&input), normalize(&output), sample.is_some())])
+        .map_err(ErrorInternalServerError)?;
+
+    Ok(redirect(problem.id))
+}
+
+fn edit((state, ids): (State<AppState>, Path<(i32, i32)>)) -> Result<impl Responder> {
+    let (pid, tid) = ids.into_inner();
+    let problem = get_problem(&state.db, pid)?;
+    let test = get_test(&state.db, pid, tid)?;
+
+    let mut obj = Object::new();
+    obj.insert("problem".into(), Value::Object(problem.to_liquid(false)));
+    obj.insert("test".into(), Value::Object(test.to_liquid()));
+    Ok(Template::render("admin/test.liquid", obj))
+}
+
+fn edit_form((state, ids, form): (State<AppState>, Path<(i32, i32)>, Form<TestForm>)) -> Result<impl Responder> {
+    let (pid, tid) = ids.into_inner();
+    let TestForm { input, output, sample } = form.into_inner();
+
+    diesel::update(test_case::table.filter(test_case::id.eq(tid)).filter(test_case::pid.eq(pid)))
+        .set((
+            test_case::input.eq(normalize(&input)),
+            test_case::output.eq(normalize(&output)),
+            test_case::sample.eq(sample.is_some()),
+        ))
+        .execute(&state.db)
+        .map_err(ErrorInternalServerError)?;
+
+    Ok(redirect(pid))
+}
+
+/// Swaps the position of a test with the test before or after it.
+fn swap(db: &SqliteConnection, pid: i32, tid: i32, up: bool) -> Result<HttpResponse> {
+    let test = get_test(db, pid, tid)?;
+
+    db.transaction::<_, diesel::result::Error, _>(|| {
+            let other = if up {
+                test_case::table
+                    .filter(test_case::pid.eq(pid))
+                    .filter(test_case::position.lt(test.position))
+                    .order(test_case::position.desc())
+                    .first::<TestCase>(db)
+                    .optional()?
+            } else {
+                test_case::table
+                    .filter(test_case::pid.eq(pid))
+                    .filter(test_case::position.gt(test.position))
+                    .order(test_case::position.asc())
+                    .first::<TestCase>(db)
+                    .optional()?
+            };
+
+            if let Some(other) = other {
+                diesel::update(test_case::table.filter(test_case::id.eq(test.id)))
+                    .set(test_case::position.eq(other.position))
+                    .execute(db)?;
+
+                diesel::update(test_case::table.filter(test_case::id.eq(other.id)))
+                    .set(test_case::position.eq(test.position))
+                    .execute(db)?;
+            }
+
+            Ok(())
+        })
+        .map_err(ErrorInternalServerError)?;
+
+    Ok(redirect(pid))
+}
+
+fn move_up((state, ids): (State<AppState>, Path<(i32, i32)>)) -> Result<impl Responder> {
+    let (pid, tid) = ids.into_inner();
+    swap(&state.db, pid, tid, true)
+}
+
+fn move_down((state, ids): (State<AppState>, Path<(i32, i32)>)) -> Result<impl Responder> {
+    let (pid, tid) = ids.into_inner();
+    swap(&state.db, pid, tid, false)
+}
+
+fn delete((state, ids): (State<AppState>, Path<(i32, i32)>)) -> Result<impl Responder> {
+    let (pid, tid) = ids.into_inner();
+    let test = get_test(&state.db, pid, tid)?;
+
+    let mut obj = Object::new();
+    obj.insert("confirmation".into(), Value::scalar(format!("Are you sure you want to delete test {}?", test.position)));
+    obj.insert("url".into(), Value::scalar(format!("/admin/problems/{}/tests/{}/delete/confirm", pid, test.id)));
+    Ok(Template::render("confirm.liquid", obj))
+}
+
+fn delete_confirm((state, ids): (State<AppState>, Path<(i32, i32)>)) -> Result<impl Responder> {
+    let (pid, tid) = ids.into_inner();
+
+    diesel::delete(test_case::table.filter(test_case::id.eq(tid)).filter(test_case::pid.eq(pid)))
+        .execute(&state.db)
+        .map_err(ErrorInternalServerError)?;
+
+    Ok(redirect(pid))
+}
+
+/// Uploads pairs of test files. Inputs end with `.in` and outputs with
+/// `.out` or `.ans`, files with the same name are paired up and added in
+/// order of their names.
+fn upload((req, pid): (HttpRequest<AppState>, Path<i32>)) -> Box<Future<Item = HttpResponse, Error = Error>> {
+    let pid = *pid;
+
+    let resp = multipart::collect(&req, UPLOAD_LIMIT)
+        .and_then(move |parts| {
+            let db = &req.state().db;
+            let problem = get_problem(db, pid)?;
+
+            let sample = parts.iter().any(|part| part.name == "sample");
+
+            let mut pairs = BTreeMap::new();
+            for part in parts.iter().filter(|part| part.name == "files") {
+                let filename = match part.filename {
+                    Some(ref filename) => filename,
+                    None => continue,
+                };
+
+                let (stem, ext) = match filename.rfind('.') {
+                    Some(i) => (&filename[..i], &filename[i + 1..]),
+                    None => (filename.as_str(), ""),
+                };
+
+                let pair = pairs.entry(stem.to_string()).or_insert((None, None));
+                match ext {
+                    "in" => pair.0 = Some(normalize(&part.text())),
+                    "out" | "ans" => pair.1 = Some(normalize(&part.text())),
+                    _ => (),
+                }
+            }
+
+            let mut tests = Vec::new();
+            let mut unmatched = Vec::new();
+            for (stem, pair) in pairs {
+                match pair {
+                    (Some(input), Some(output)) => {
+                        let sample = sample || stem.starts_with("sample");
+                        tests.push((input, output, sample));
+                    }
+                    _ => unmatched.push(stem),
+                }
+            }
+
+            let count = tests.len();
+            append(db, problem.id, tests).map_err(ErrorInternalServerError)?;
+
+            let session = req.session();
+            session.set("success", format!("Uploaded {} tests.", count))?;
+            if !unmatched.is_empty() {
+                session.set("error", format!("These files had no matching input or output: {}", unmatched.join(", ")))?;
+            }
+
+            Ok(redirect(problem.id))
+        });
+
+    Box::new(resp)
+}
+
+/// Gets the fields of a form for a program written by an admin. Empty file
+/// inputs are left out so the code can be typed in instead.
+fn program_fields(parts: Vec<multipart::Part>) -> HashMap<String, multipart::Part> {
+    parts.into_iter()
+        .filter(|part| part.filename.as_ref().map(|name| !name.is_empty()).unwrap_or(true))
+        .map(|part| (part.name.clone(), part))
+        .collect()
+}
+
+/// Sets how the output of a problem's tests is checked. The code of a
+/// custom checker can be typed in or uploaded as a file.
+fn checker((req, pid): (HttpRequest<AppState>, Path<i32>)) -> Box<Future<Item = HttpResponse, Error = Error>> {
+    let pid = *pid;
+
+    let resp = multipart::collect(&req, UPLOAD_LIMIT)
+        .and_then(move |parts| {
+            let db = &req.state().db;
+            let problem = get_problem(db, pid)?;
+
+            let fields = program_fields(parts);
+            let field = |name: &str| fields.get(name).map(|part| part.text()).unwrap_or_default();
+
+            let mode = field("checker");
+            let tolerance = field("tolerance").trim().parse::<f64>().unwrap_or(1e-6);
+            let lid = field("checker_language").parse::<i32>().ok();
+            let code = match fields.get("checker_file") {
+                Some(file) => file.text(),
+                None => normalize(&field("checker_code")),
+            };
+
+            let session = req.session();
+
+            if !Checker::MODES.iter().any(|&(m, _)| m == mode) {
+                session.set("error", "Unknown checker.")?;
+            } else if (mode == "custom" || mode == "kattis") && (lid.is_none() || code.trim().is_empty()) {
+                session.set("error", "A custom checker needs a language and code.")?;
+            } else {
+                diesel::update(problem::table.filter(problem::id.eq(problem.id)))
+                    .set((
+                        problem::checker.eq(mode),
+                        problem::tolerance.eq(tolerance),
+                        problem::checker_language.eq(lid),
+                        problem::checker_code.eq(code),
+                    ))
+                    .execute(db)
+                    .map_err(ErrorInternalServerError)?;
+
+                session.set("success", "The checker was saved.")?;
+            }
+
+            Ok(redirect(problem.id))
+        });
+
+    Box::new(resp)
+}
+
+/// Makes a problem interactive or not and sets its interactor. The code of
+/// the interactor can be typed in or uploaded as a file.
+fn interactor((req, pid): (HttpRequest<AppState>, Path<i32>)) -> Box<Future<Item = HttpResponse, Error = Error>> {
+    let pid = *pid;
+
+    let resp = multipart::collect(&req, UPLOAD_LIMIT)
+        .and_then(move |parts| {
+            let db = &req.state().db;
+            let problem = get_problem(db, pid)?;
+
+            let fields = program_fields(parts);
+            let field = |name: &str| fields.get(name).map(|part| part.text()).unwrap_or_default();
+
+            let interactive = fields.contains_key("interactive");
+            let convention = field("interactor_convention");
+            let lid = field("interactor_language").parse::<i32>().ok();
+            let code = match fields.get("interactor_file") {
+                Some(file) => file.text(),
+                None => normalize(&field("interactor_code")),
+            };
+
+            let session = req.session();
+
+            if !Convention::MODES.iter().any(|&(m, _)| m == convention) {
+                session.set("error", "Unknown interactor convention.")?;
+            } else if interactive && (lid.is_none() || code.trim().is_empty()) {
+                session.set("error", "An interactive problem needs an interactor language and code.")?;
+            } else {
+                diesel::update(problem::table.filter(problem::id.eq(problem.id)))
+                    .set((
+                        problem::interactive.eq(interactive),
+                        problem::interactor_convention.eq(convention),
+                        problem::interactor_language.eq(lid),
+                        problem::interactor_code.eq(code),
+                    ))
+                    .execute(db)
+                    .map_err(ErrorInternalServerError)?;
+
+                session.set("success", "The interactor was saved.")?;
+            }
+
+            Ok(redirect(problem.id))
+        });
+
+    Box::new(resp)
+}
+
+/// Sets the problem's reference solution, which has to be accepted before
+/// the problem can be published. The code can be typed in or uploaded as a
+/// file.
+fn reference((req, pid): (HttpRequest<AppState>, Path<i32>)) -> Box<Future<Item = HttpResponse, Error = Error>> {
+    let pid = *pid;
+
+    let resp = multipart::collect(&req, UPLOAD_LIMIT)
+        .and_then(move |parts| {
+            let db = &req.state().db;
+            let problem = get_problem(db, pid)?;
+
+            let fields = program_fields(parts);
+            let field = |name: &str| fields.get(name).map(|part| part.text()).unwrap_or_default();
+
+            let lid = field("reference_language").parse::<i32>().ok();
+            let code = match fields.get("reference_file") {
+                Some(file) => file.text(),
+                None => normalize(&field("reference_code")),
+            };
+
+            let session = req.session();
+
+            if lid.is_none() && !code.trim().is_empty() {
+                session.set("error", "The reference solution needs a language.")?;
+            } else {
+                diesel::update(problem::table.filter(problem::id.eq(problem.id)))
+                    .set((
+                        problem::reference_language.eq(lid),
+                        problem::reference_code.eq(code),
+                    ))
+                    .execute(db)
+                    .map_err(ErrorInternalServerError)?;
+
+                session.set("success", "The reference solution was saved.")?;
+            }
+
+            Ok(redirect(problem.id))
+        });
+
+    Box::new(resp)
+}
@@ -0,0 +1,197 @@
+//! The server's side of the judge node protocol, see `judge::protocol`.
+
+use actix_web::{
+    Error, Result, App, HttpMessage, HttpRequest, HttpResponse, Path,
+    error::{ ErrorBadRequest, ErrorForbidden, ErrorInternalServerError, ErrorNotFound },
+    http::header::AUTHORIZATION,
+};
+use futures::{ future, Future };
+use serde::de::DeserializeOwned;
+
+use ::{
+    AppState,
+    judge::{
+        load_package, nodes,
+        protocol::{
+            self, VERSION,
+            Fetch, GiveBack, Heartbeat, Judged, PackageRef, PackageUpload, Ran, Register, Registered, Saved, Work,
+        },
+        queue::Task,
+    },
+};
+
+/// The largest message a node can send, reports have the output of every
+/// sample test in them.
+const MESSAGE_LIMIT: usize = 16 * 1024 * 1024;
+
+pub fn configure(app: App<AppState>) -> App<AppState> {
+    app.scope(protocol::PREFIX, |s| {
+            s.resource("/register", |r| r.post().with_async(register))
+                .resource("/heartbeat", |r| r.post().with_async(heartbeat))
+                .resource("/work", |r| r.post().with_async(work))
+                .resource("/packages/{pid}/{lid}", |r| r.get().with(package))
+                .resource("/judged", |r| r.post().with_async(judged))
+                .resource("/ran", |r| r.post().with_async(ran))
+                .resource("/giveback", |r| r.post().with_async(give_back))
+        })
+}
+
+/// Checks that the request comes from a node with the shared token.
+fn authorize(req: &HttpRequest<AppState>) -> Result<()> {
+    let token = req.state().node_token.as_ref()
+        .ok_or_else(|| ErrorForbidden("Judge nodes aren't enabled."))?;
+
+    let given = req.headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| value.starts_with("Bearer "))
+        .map(|value| &value[7..])
+        .unwrap_or("");
+
+    // Every byte is compared so the time taken doesn't give the token away.
+    let matches = given.len() == token.len() && given.bytes()
+        .zip(token.bytes())
+        .fold(0, |diff, (a, b)| diff | (a ^ b)) == 0;
+
+    if matches {
+        Ok(())
+    } else {
+        Err(ErrorForbidden("Invalid token."))
+    }
+}
+
+/// Reads a message from a node.
+fn receive<T>(req: &HttpRequest<AppState>) -> Box<Future<Item = T, Error = Error>>
+    where T: DeserializeOwned + 'static
+{
+    if let Err(err) = authorize(req) {
+        return Box::new(future::err(err));
+    }
+
+    Box::new(req.json().limit(MESSAGE_LIMIT).from_err())
+}
+
+fn register(req: HttpRequest<AppState>) -> Box<Future<Item = HttpResponse, Error = Error>> {
+    let resp = receive::<Register>(&req)
+        .and_then(move |msg| {
+            if msg.version != VERSION {
+                return Err(ErrorBadRequest(format!("The server uses version {} of the protocol.", VERSION)));
+            }
+
+            if !nodes::valid_name(&msg.node) {
+                return Err(ErrorBadRequest("Node names can only have letters, numbers, dots, dashes and underscores."));
+            }
+
+            let state = req.state();
+            nodes::register(&state.db, &state.dispatcher, &msg.node, msg.workers)
+                .map_err(ErrorInternalServerError)?;
+
+            info!("Judge node {} registered with {} workers", msg.node, msg.workers);
+            Ok(HttpResponse::Ok().json(Registered { heartbeat: nodes::HEARTBEAT_INTERVAL.as_secs() }))
+        });
+
+    Box::new(resp)
+}
+
+fn heartbeat(req: HttpRequest<AppState>) -> Box<Future<Item = HttpResponse, Error = Error>> {
+    let resp = receive::<Heartbeat>(&req)
+        .and_then(move |msg| {
+            let known = nodes::seen(&req.state().db, &msg.node)
+                .map_err(ErrorInternalServerError)?;
+
+            if !known {
+                return Err(ErrorNotFound("Unknown node."));
+            }
+
+            Ok(HttpResponse::Ok().json(Registered { heartbeat: nodes::HEARTBEAT_INTERVAL.as_secs() }))
+        });
+
+    Box::new(resp)
+}
+
+fn work(req: HttpRequest<AppState>) -> Box<Future<Item = HttpResponse, Error = Error>> {
+    let resp = receive::<Fetch>(&req)
+        .and_then(move |msg| {
+            let state = req.state();
+            let known = nodes::seen(&state.db, nodes::node_of(&msg.worker))
+                .map_err(ErrorInternalServerError)?;
+
+            if !known {
+                return Err(ErrorNotFound("Unknown node."));
+            }
+
+            let task = state.dispatcher.next(&state.db, &msg.worker)
+                .map_err(ErrorInternalServerError)?;
+
+            // Packages are sent separately so nodes can keep them around.
+            let work = match task {
+                Some(Task::Judge { entry, job }) => Work::Judge {
+                    entry: entry,
+                    sid: job.sid,
+                    language: job.language,
+                    toolchain: job.toolchain,
+                    files: job.files,
+                    package: PackageRef {
+                        pid: job.pid,
+                        lid: job.lid,
+                        digest: protocol::digest(&job.package),
+                    },
+                },
+                Some(Task::Run { id, task }) => Work::Run { id: id, task: task },
+                None => Work::Idle,
+            };
+
+            Ok(HttpResponse::Ok().json(work))
+        });
+
+    Box::new(resp)
+}
+
+fn package((req, ids): (HttpRequest<AppState>, Path<(i32, i32)>)) -> Result<HttpResponse> {
+    authorize(&req)?;
+
+    let (pid, lid) = *ids;
+    let package = load_package(&req.state().db, pid, lid)
+        .map_err(ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().json(PackageUpload {
+        digest: protocol::digest(&package),
+        package: package,
+    }))
+}
+
+fn judged(req: HttpRequest<AppState>) -> Box<Future<Item = HttpResponse, Error = Error>> {
+    let resp = receive::<Judged>(&req)
+        .and_then(move |msg| {
+            let state = req.state();
+            let saved = state.dispatcher.judged(&state.db, &msg.worker, msg.entry, &msg.result)
+                .map_err(ErrorInternalServerError)?;
+
+            Ok(HttpResponse::Ok().json(Saved { saved: saved }))
+        });
+
+    Box::new(resp)
+}
+
+fn ran(req: HttpRequest<AppState>) -> Box<Future<Item = HttpResponse, Error = Error>> {
+    let resp = receive::<Ran>(&req)
+        .and_then(move |msg| {
+            let saved = req.state().dispatcher.ran(&msg.worker, msg.id, msg.result);
+            Ok(HttpResponse::Ok().json(Saved { saved: saved }))
+        });
+
+    Box::new(resp)
+}
+
+fn give_back(req: HttpRequest<AppState>) -> Box<Future<Item = HttpResponse, Error = Error>> {
+    let resp = receive::<GiveBack>(&req)
+        .and_then(move |msg| {
+            let state = req.state();
+            let saved = state.dispatcher.give_back(&state.db, &msg.worker, msg.entry)
+                .map_err(ErrorInternalServerError)?;
+
+            Ok(HttpResponse::Ok().json(Saved { saved: saved }))
+        });
+
+    Box::new(resp)
+}
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use actix_web::{
     Result, error::ErrorInternalServerError,
     App, Responder, State,
@@ -8,9 +10,10 @@ use liquid::{ Object, Value };
 use ::{
     AppState,
     db::{
-        models::User,
-        schema::{ user, solution },
+        models::{ ProblemState, User },
+        schema::{ problem, user, solution },
     },
+    judge::Verdict,
     middleware::Template,
 };
 
@@ -19,22 +22,35 @@ pub fn configure(app: App<AppState>) -> App<AppState> {
 }
 
 fn index(state: State<AppState>) -> Result<impl Responder> {
-    // Get the score, it's the number of solutions the user
-    // has successfully submitted.
-    let num_solutions = solution::table
-        .filter(solution::uid.eq(user::id))
-        .count()
-        .single_value();
-
-    let users = user::table
-        .select((user::all_columns, num_solutions))
-        .order_by(num_solutions.desc())
-        .load::<(User, Option<i64>)>(&state.db)
+    // The score is the number of different problems the user has solved,
+    // solving a problem again doesn't count. Drafts aren't out yet and
+    // deleted problems don't count anymore.
+    let mut scores = HashMap::new();
+    let solved = solution::table
+        .inner_join(problem::table)
+        .filter(problem::state.ne(ProblemState::Draft.code()))
+        .filter(solution::verdict.eq(Verdict::Accepted.code()))
+        .select((solution::uid, solution::pid))
+        .distinct()
+        .load::<(i32, i32)>(&state.db)
+        .map_err(ErrorInternalServerError)?;
+
+    for (uid, _) in solved {
+        *scores.entry(uid).or_insert(0) += 1;
+    }
+
+    let mut users = user::table
+        .load::<User>(&state.db)
         .map_err(ErrorInternalServerError)?
         .into_iter()
-        .map(|(user, score)| {
+        .map(|user| (scores.get(&user.id).cloned().unwrap_or(0), user))
+        .collect::<Vec<_>>();
+
+    users.sort_by(|a, b| b.0.cmp(&a.0));
+
+    let users = users.into_iter()
+        .map(|(score, user)| {
             let mut obj = user.to_liquid();
-            let score = score.unwrap_or(0) as i32;
             obj.insert("score".into(), Value::scalar(score));
             Value::Object(obj)
         });
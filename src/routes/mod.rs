@@ -2,35 +2,64 @@ use actix_web::{
     Result, error::ErrorInternalServerError,
     App, Responder, HttpRequest, State,
 };
-use diesel::prelude::*;
+use chrono::Utc;
+use diesel::{ prelude::*, sql_types::{ Nullable, Timestamp } };
 use liquid::{ Object, Value };
 
 use ::{
     AppState,
-    db::{ models::Problem, schema::problem },
+    db::{ models::{ Problem, ProblemState }, schema::problem },
     middleware::Template,
 };
 
 mod admin;
+mod judge;
 mod user;
 mod problems;
+mod solution;
 mod leaderboard;
+mod multipart;
+
+// Picks the release date of problems that have one.
+sql_function!(fn coalesce(x: Nullable<Timestamp>, y: Timestamp) -> Timestamp);
 
 pub fn configure(app: App<AppState>) -> App<AppState> {
     app.resource("/", |r| r.with(index))
         .configure(self::admin::configure)
+        .configure(self::judge::configure)
         .configure(self::user::configure)
         .configure(self::problems::configure)
+        .configure(self::solution::configure)
         .configure(self::leaderboard::configure)
         .default_resource(|r| r.with(not_found))
 }
 
+/// Shows the problem of the week, the most recently released problem that
+/// isn't due yet. Once everything is due the last problem released is shown.
+/// Problems without a release date count as released now. Drafts and
+/// archived problems are never shown.
 fn index(state: State<AppState>) -> Result<impl Responder> {
-    let prob = problem::table
+    let now = Utc::now().naive_utc();
+
+    let released = problem::table
+        .filter(problem::state.eq(ProblemState::Published.code()))
+        .filter(problem::release_at.is_null().or(problem::release_at.le(now)))
+        .order((coalesce(problem::release_at, now).desc(), problem::id.desc()));
+
+    let active = released.clone()
+        .filter(problem::due_at.is_null().or(problem::due_at.gt(now)))
         .first::<Problem>(&state.db)
         .optional()
-        .map_err(ErrorInternalServerError)?
-        .map(|prob| prob.to_liquid(true));
+        .map_err(ErrorInternalServerError)?;
+
+    let prob = match active {
+        Some(prob) => Some(prob),
+        None => released
+            .first::<Problem>(&state.db)
+            .optional()
+            .map_err(ErrorInternalServerError)?,
+    };
+    let prob = prob.map(|prob| prob.to_liquid(true));
 
     let mut obj = Object::new();
 
@@ -0,0 +1,68 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use actix_web::{
+    Error, HttpMessage, HttpRequest,
+    error::ErrorBadRequest,
+    multipart::MultipartItem,
+};
+use futures::{ future, Future, Stream };
+
+/// A single field of a multipart form.
+pub struct Part {
+    pub name: String,
+    /// The name of the uploaded file if the field was a file input.
+    pub filename: Option<String>,
+    pub data: Vec<u8>,
+}
+
+impl Part {
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.data).into_owned()
+    }
+}
+
+/// Reads every field of a multipart form into memory, rejecting the upload
+/// once more than `limit` bytes have been read.
+pub fn collect<S: 'static>(req: &HttpRequest<S>, limit: usize) -> Box<Future<Item = Vec<Part>, Error = Error>> {
+    let size = Rc::new(Cell::new(0));
+
+    let parts = req.multipart()
+        .map_err(Error::from)
+        .and_then(move |item| -> Box<Future<Item = Option<Part>, Error = Error>> {
+            match item {
+                MultipartItem::Field(field) => {
+                    let (name, filename) = field.content_disposition()
+                        .map(|cd| (cd.get_name().map(Into::into), cd.get_filename().map(Into::into)))
+                        .unwrap_or((None, None));
+
+                    let size = size.clone();
+                    let data = field.map_err(Error::from)
+                        .fold(Vec::new(), move |mut data, bytes| {
+                            size.set(size.get() + bytes.len());
+
+                            if size.get() > limit {
+                                Err(ErrorBadRequest("The upload is too large."))
+                            } else {
+                                data.extend_from_slice(&bytes);
+                                Ok(data)
+                            }
+                        });
+
+                    Box::new(data.map(move |data| {
+                        name.map(|name| Part {
+                            name: name,
+                            filename: filename,
+                            data: data,
+                        })
+                    }))
+                }
+                // Browsers don't send nested multipart bodies anymore.
+                MultipartItem::Nested(_) => Box::new(future::ok(None)),
+            }
+        })
+        .filter_map(|part| part)
+        .collect();
+
+    Box::new(parts)
+}
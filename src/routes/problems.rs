@@ -1,57 +1,553 @@
+use std::collections::{ BTreeMap, BTreeSet, HashMap };
+
 use actix_web::{
-    Result, App, Responder, Path, State,
+    Result, App, Error, FromRequest, Responder, Path, Query, State, Form, HttpRequest, HttpResponse,
     error::{
+        ErrorBadRequest,
         ErrorInternalServerError,
         ErrorNotFound,
-    }
+        ErrorServiceUnavailable,
+    },
+    middleware::session::{ RequestSession, Session },
 };
-use diesel::prelude::*;
+use chrono::{ Datelike, NaiveDate, Weekday };
+use diesel::{ self, prelude::* };
+use futures::{ future, Future };
 use liquid::{ Object, Value };
 
 use ::{
     AppState,
     db::{
-        models::{ Language, Problem },
-        schema::{ language, problem },
+        immediate_transaction, last_insert_rowid,
+        models::{ Language, Problem, ProblemState, TestCase, User },
+        schema::{ language, problem, problem_language, solution, solution_file, test_case },
     },
+    judge::{ MAX_FILES, MAX_SIZE, Judge, SourceFile, Toolchain, Verdict, queue, is_archive, load_limits, valid_name },
     middleware::Template,
+    quota::Quota,
+    routes::multipart,
+    tags,
+    visibility::can_see_problem,
 };
 
+/// The largest upload of solution files we'll accept. Archives are
+/// compressed so this is a bit more than the size allowed for solutions.
+const UPLOAD_LIMIT: usize = 2 * MAX_SIZE as usize;
+
 pub fn configure(app: App<AppState>) -> App<AppState> {
     app.scope("/problems", |s| {
             s.resource("/", |r| r.with(index))
-                .resource("/{id}/", |r| r.with(problem))
+                .resource("/{id}/", |r| {
+                    r.get().with(problem);
+                    r.post().with(submit);
+                })
+                .resource("/{id}/upload", |r| r.post().with_async(upload))
+                .resource("/{id}/run", |r| r.post().with_async(run))
         })
 }
 
-fn index(state: State<AppState>) -> Result<impl Responder> {
+#[derive(Deserialize)]
+struct ListQuery {
+    /// Only problems with this tag are listed.
+    tag: Option<String>,
+    /// Only problems of this difficulty are listed.
+    difficulty: Option<String>,
+    /// One of `SORTS`, the problems are sorted by release date if it's left
+    /// out.
+    sort: Option<String>,
+}
+
+/// The ways the problem list can be sorted and their descriptions.
+const SORTS: &'static [(&'static str, &'static str)] = &[
+    ("release", "Release date"),
+    ("solves", "Most solved"),
+    ("difficulty", "Easiest first"),
+];
+
+/// The difficulties a problem can be rated with, for picking one.
+pub fn difficulties() -> Value {
+    let difficulties = Problem::DIFFICULTIES.iter()
+        .map(|&(level, name)| {
+            let mut obj = Object::new();
+            obj.insert("level".into(), Value::scalar(level));
+            obj.insert("name".into(), Value::scalar(name));
+            Value::Object(obj)
+        });
+
+    Value::array(difficulties)
+}
+
+/// Lists the problems by the week they're released in, newest first.
+/// Problems without a release date are listed last. The list can be filtered
+/// by tag and difficulty, and sorted by the number of users who solved each
+/// problem or by difficulty instead, which lists the problems ungrouped.
+fn index((req, query): (HttpRequest<AppState>, Query<ListQuery>)) -> Result<impl Responder> {
+    let db = &req.state().db;
+    let current_user = req.extensions().get::<User>().cloned();
+
+    // The filter form leaves fields empty rather than out.
+    let param = |value: &Option<String>| value.as_ref()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty());
+
+    let tag = param(&query.tag).map(|tag| tag.to_lowercase());
+    let difficulty = match param(&query.difficulty) {
+        Some(difficulty) => Some(difficulty.parse::<i32>().map_err(|_| ErrorBadRequest("Invalid difficulty."))?),
+        None => None,
+    };
+    let sort = param(&query.sort).unwrap_or_else(|| "release".into());
+    if !SORTS.iter().any(|&(name, _)| name == sort) {
+        return Err(ErrorBadRequest("Invalid sort."));
+    }
+
     let problems = problem::table
-        .load::<Problem>(&state.db)
-        .map_err(ErrorInternalServerError)?
-        .into_iter()
-        .map(|prob| Value::Object(prob.to_liquid(false)));
+        .order((problem::release_at.asc(), problem::id.asc()))
+        .load::<Problem>(db)
+        .map_err(ErrorInternalServerError)?;
+
+    let mut problem_tags = tags::load(db)
+        .map_err(ErrorInternalServerError)?;
+
+    // Like the leaderboard, solving a problem again doesn't count.
+    let mut solves = HashMap::new();
+    let solved = solution::table
+        .filter(solution::verdict.eq(Verdict::Accepted.code()))
+        .select((solution::pid, solution::uid))
+        .distinct()
+        .load::<(i32, i32)>(db)
+        .map_err(ErrorInternalServerError)?;
+
+    for (pid, _) in solved {
+        *solves.entry(pid).or_insert(0) += 1;
+    }
+
+    // Only the tags of problems the user can see are offered as filters.
+    let mut all_tags = BTreeSet::new();
+    let mut problems = problems.into_iter()
+        .filter(|problem| can_see_problem(current_user.as_ref(), problem))
+        .map(|problem| {
+            let tags = problem_tags.remove(&problem.id).unwrap_or_default();
+            all_tags.extend(tags.iter().cloned());
+            let solves = solves.get(&problem.id).cloned().unwrap_or(0);
+            (problem, tags, solves)
+        })
+        .filter(|&(ref problem, ref tags, _)| {
+            tag.as_ref().map(|tag| tags.contains(tag)).unwrap_or(true)
+                && difficulty.map(|difficulty| problem.difficulty == Some(difficulty)).unwrap_or(true)
+        })
+        .collect::<Vec<_>>();
+
+    // The sorts are stable, so ties stay in release order.
+    match &sort[..] {
+        "solves" => problems.sort_by(|a, b| b.2.cmp(&a.2)),
+        "difficulty" => problems.sort_by_key(|&(ref problem, _, _)| problem.difficulty.unwrap_or(i32::max_value())),
+        _ => {}
+    }
+
+    let grouped = sort == "release";
+    let mut weeks = BTreeMap::<Option<NaiveDate>, Vec<Value>>::new();
+    let mut sorted = Vec::new();
+    for (problem, tags, solves) in problems {
+        let week = problem.release_at.map(|release_at| {
+            let week = release_at.date().iso_week();
+            NaiveDate::from_isoywd(week.year(), week.week(), Weekday::Mon)
+        });
+
+        let mut obj = problem.to_liquid(false);
+        obj.insert("tags".into(), Value::array(tags.into_iter().map(Value::scalar)));
+        obj.insert("solves".into(), Value::scalar(solves));
+
+        if grouped {
+            weeks.entry(week)
+                .or_insert_with(Vec::new)
+                .push(Value::Object(obj));
+        } else {
+            sorted.push(Value::Object(obj));
+        }
+    }
+
+    // `None` sorts first, so going backwards puts the unscheduled problems
+    // at the end.
+    let mut weeks = weeks.into_iter()
+        .rev()
+        .map(|(week, problems)| {
+            let mut obj = Object::new();
+            if let Some(week) = week {
+                obj.insert("start".into(), Value::scalar(week.format("%B %-d, %Y").to_string()));
+            }
+            obj.insert("problems".into(), Value::array(problems));
+            Value::Object(obj)
+        })
+        .collect::<Vec<_>>();
+
+    if !sorted.is_empty() {
+        let mut obj = Object::new();
+        obj.insert("problems".into(), Value::array(sorted));
+        weeks.push(Value::Object(obj));
+    }
+
+    let sorts = SORTS.iter()
+        .map(|&(name, description)| {
+            let mut obj = Object::new();
+            obj.insert("name".into(), Value::scalar(name));
+            obj.insert("description".into(), Value::scalar(description));
+            Value::Object(obj)
+        });
 
     let mut obj = Object::new();
-    obj.insert("problems".into(), Value::array(problems));
+    obj.insert("weeks".into(), Value::array(weeks));
+    obj.insert("grouped".into(), Value::scalar(grouped));
+    obj.insert("filtered".into(), Value::scalar(tag.is_some() || difficulty.is_some()));
+    obj.insert("tags".into(), Value::array(all_tags.into_iter().map(Value::scalar)));
+    obj.insert("difficulties".into(), difficulties());
+    obj.insert("sorts".into(), Value::array(sorts));
+    if let Some(tag) = tag {
+        obj.insert("tag".into(), Value::scalar(tag));
+    }
+    if let Some(difficulty) = difficulty {
+        obj.insert("difficulty".into(), Value::scalar(difficulty));
+    }
+    obj.insert("sort".into(), Value::scalar(sort));
     Ok(Template::render("problems/index.liquid", obj))
 }
 
-fn problem((state, id): (State<AppState>, Path<i32>)) -> Result<impl Responder> {
+fn problem((req, id): (HttpRequest<AppState>, Path<i32>)) -> Result<impl Responder> {
+    let state = req.state();
+    let current_user = req.extensions().get::<User>().cloned();
+
     let problem = problem::table
         .filter(problem::id.eq(*id))
         .first::<Problem>(&state.db)
         .optional()
         .map_err(ErrorInternalServerError)?
+        .filter(|problem| can_see_problem(current_user.as_ref(), problem))
         .ok_or_else(|| ErrorNotFound("No problem found."))?;
 
+    page(state, current_user, problem, false)
+}
+
+/// Renders a problem's page. Admins preview problems with the notes only
+/// they would see left out, so the page looks like it does for students.
+pub fn page(state: &AppState, current_user: Option<User>, problem: Problem, preview: bool) -> Result<Template> {
     let languages = language::table
+        .filter(language::enabled.eq(true))
         .load::<Language>(&state.db)
         .map_err(ErrorInternalServerError)?
         .into_iter()
         .map(|lang| Value::Object(lang.to_liquid()));
 
+    // Languages that get more time are listed with the time limit.
+    let multipliers = problem_language::table
+        .inner_join(language::table)
+        .filter(problem_language::pid.eq(problem.id))
+        .filter(problem_language::time_multiplier.ne(1.0))
+        .select((language::name, problem_language::time_multiplier))
+        .load::<(String, f64)>(&state.db)
+        .map_err(ErrorInternalServerError)?
+        .into_iter()
+        .map(|(name, multiplier)| {
+            let mut obj = Object::new();
+            obj.insert("name".into(), Value::scalar(name));
+            obj.insert("multiplier".into(), Value::scalar(multiplier));
+            Value::Object(obj)
+        });
+
+    let samples = test_case::table
+        .filter(test_case::pid.eq(problem.id))
+        .filter(test_case::sample.eq(true))
+        .order(test_case::position.asc())
+        .load::<TestCase>(&state.db)
+        .map_err(ErrorInternalServerError)?
+        .into_iter()
+        .map(|test| Value::Object(test.to_liquid()));
+
+    let tags = tags::for_problem(&state.db, problem.id)
+        .map_err(ErrorInternalServerError)?;
+
     let mut obj = Object::new();
+    if let Some(user) = current_user {
+        let quota = Quota::check(&state.db, &user, &problem)
+            .map_err(ErrorInternalServerError)?;
+        obj.insert("quota".into(), Value::Object(quota.to_liquid()));
+    }
     obj.insert("problem".into(), Value::Object(problem.to_liquid(true)));
+    obj.insert("tags".into(), Value::array(tags.into_iter().map(Value::scalar)));
     obj.insert("languages".into(), Value::array(languages));
+    obj.insert("multipliers".into(), Value::array(multipliers));
+    obj.insert("samples".into(), Value::array(samples));
+    obj.insert("preview".into(), Value::scalar(preview));
     Ok(Template::render("problems/problem.liquid", obj))
 }
+
+#[derive(Deserialize)]
+struct SolutionForm {
+    language: i32,
+    code: String,
+}
+
+fn submit(req: (State<AppState>, Session, User, Path<i32>, Form<SolutionForm>)) -> Result<HttpResponse> {
+    let (state, session, user, id, form) = req;
+    let SolutionForm { language, code } = form.into_inner();
+    let problem = get_problem(&state.db, *id, &user)?;
+
+    create(&state.db, &session, &user, &problem, Some(language), code, Vec::new())
+}
+
+/// Submits a solution made of uploaded files, or a single archive of them.
+fn upload((req, id): (HttpRequest<AppState>, Path<i32>)) -> Box<Future<Item = HttpResponse, Error = Error>> {
+    let pid = *id;
+
+    let resp = multipart::collect(&req, UPLOAD_LIMIT)
+        .and_then(move |parts| -> Result<_> {
+            let user = User::extract(&req)?;
+            let problem = get_problem(&req.state().db, pid, &user)?;
+            Ok((req, user, problem, parts))
+        })
+        .and_then(|(req, user, problem, parts)| {
+            let language = parts.iter()
+                .find(|part| part.name == "language")
+                .and_then(|part| part.text().parse::<i32>().ok());
+
+            // An empty file input is still sent, without a file name.
+            let uploads = parts.into_iter()
+                .filter(|part| part.name == "files")
+                .filter(|part| part.filename.as_ref().map(|name| !name.is_empty()).unwrap_or(false))
+                .collect::<Vec<_>>();
+
+            let files = read_files(&req.state().judge, uploads);
+            files.then(move |files| {
+                let session = req.session();
+                match files {
+                    Ok(files) => create(&req.state().db, &session, &user, &problem, language, String::new(), files),
+                    Err(error) => back(&session, problem.id, error),
+                }
+            })
+        });
+
+    Box::new(resp)
+}
+
+fn filename(part: &multipart::Part) -> String {
+    part.filename.clone().unwrap_or_default()
+}
+
+/// Gets the source files out of the uploaded files. Archives are extracted
+/// off of the handler's thread.
+fn read_files(judge: &Judge, mut uploads: Vec<multipart::Part>) -> Box<Future<Item = Vec<SourceFile>, Error = String>> {
+    if uploads.len() == 1 && is_archive(&filename(&uploads[0])) {
+        let upload = uploads.remove(0);
+        let files = judge.extract(filename(&upload), upload.data)
+            .map_err(|err| err.to_string());
+
+        return Box::new(files);
+    }
+
+    Box::new(future::result(name_files(&uploads)))
+}
+
+/// Uses the uploaded files as they are, named after the files they were
+/// uploaded from.
+fn name_files(uploads: &[multipart::Part]) -> ::std::result::Result<Vec<SourceFile>, String> {
+    let mut files = Vec::<SourceFile>::new();
+    for part in uploads {
+        // Browsers shouldn't send the path of a file but not all of them
+        // can be trusted not to.
+        let name = filename(part)
+            .rsplit(|c| c == '/' || c == '\\')
+            .next()
+            .unwrap_or("")
+            .to_string();
+
+        if !valid_name(&name) {
+            return Err(format!("\"{}\" isn't a valid file name.", name));
+        }
+        if files.iter().any(|file| file.name == name) {
+            return Err(format!("You uploaded more than one file named {}.", name));
+        }
+
+        files.push(SourceFile { name: name, content: part.text() });
+    }
+
+    let size = files.iter().map(|file| file.content.len() as u64).sum::<u64>();
+    if files.len() > MAX_FILES {
+        Err(format!("Solutions can't have more than {} files.", MAX_FILES))
+    } else if size > MAX_SIZE {
+        Err(format!("Solutions can't be larger than {} KB.", MAX_SIZE / 1024))
+    } else {
+        Ok(files)
+    }
+}
+
+/// Saves a solution and queues it to be judged if the user is allowed to
+/// submit it. Solutions with more than one file are saved as `files`
+/// instead of `code`.
+fn create(db: &SqliteConnection, session: &Session, user: &User, problem: &Problem, language: Option<i32>, code: String, files: Vec<SourceFile>) -> Result<HttpResponse> {
+    let lang = match language {
+        Some(language) => language::table
+            .filter(language::id.eq(language))
+            .first::<Language>(db)
+            .optional()
+            .map_err(ErrorInternalServerError)?,
+        None => None,
+    };
+
+    // If the submission isn't valid we'll send the user back to the problem
+    // and let them know what went wrong.
+    let error = match lang {
+        _ if problem.state() == ProblemState::Archived => Some("This problem is archived and isn't accepting solutions.".into()),
+        None => Some("That language doesn't exist.".into()),
+        Some(ref lang) if !lang.enabled => Some("That language isn't accepting solutions.".into()),
+        Some(_) if code.trim().is_empty() && files.is_empty() => Some("Your solution is empty.".into()),
+        Some(ref lang) if !files.is_empty() && !files.iter().any(|file| file.name == lang.source) => {
+            Some(format!("One of your files has to be named {}, it's the one that gets compiled and run.", lang.source))
+        }
+        Some(_) => None,
+    };
+
+    if let Some(error) = error {
+        return back(session, problem.id, error);
+    }
+
+    let lang = lang.unwrap();
+
+    // The quota is checked in the transaction the solution is saved in, so
+    // submitting several times at once can't go over it. Each submission
+    // waits for the others to be saved before checking.
+    let saved = immediate_transaction::<_, diesel::result::Error, _>(db, || {
+            if let Some(error) = Quota::check(db, user, problem)?.error() {
+                return Ok(Err(error));
+            }
+
+            diesel::insert_into(solution::table)
+                .values((
+                    solution::pid.eq(problem.id),
+                    solution::uid.eq(user.id),
+                    solution::language.eq(lang.id),
+                    solution::code.eq(code),
+                ))
+                .execute(db)?;
+
+            let sid = diesel::select(last_insert_rowid).get_result::<i32>(db)?;
+
+            for file in &files {
+                diesel::insert_into(solution_file::table)
+                    .values((
+                        solution_file::sid.eq(sid),
+                        solution_file::name.eq(&file.name),
+                        solution_file::content.eq(&file.content),
+                    ))
+                    .execute(db)?;
+            }
+
+            queue::enqueue(db, sid)?;
+            Ok(Ok(sid))
+        })
+        .map_err(ErrorInternalServerError)?;
+
+    let sid = match saved {
+        Ok(sid) => sid,
+        Err(error) => return back(session, problem.id, error),
+    };
+
+    session.set("success", "Your solution has been submitted and is waiting to be judged.")?;
+
+    Ok(HttpResponse::Found()
+        .header("location", format!("/solution/{}/", sid))
+        .finish())
+}
+
+#[derive(Deserialize)]
+struct RunForm {
+    language: i32,
+    code: String,
+    input: String,
+}
+
+/// What happened when code was run with custom input.
+#[derive(Serialize)]
+struct RunResult {
+    /// The compiler output if compiling failed.
+    compile_error: Option<String>,
+    status: String,
+    success: bool,
+    time: u64,
+    memory: u64,
+    stdout: String,
+    stderr: String,
+}
+
+/// Runs code with the user's input without submitting it, so it doesn't
+/// count against their attempts or show up anywhere.
+fn run((state, user, id, form): (State<AppState>, User, Path<i32>, Form<RunForm>)) -> Box<Future<Item = HttpResponse, Error = Error>> {
+    let RunForm { language, code, input } = form.into_inner();
+
+    let db = &state.db;
+
+    let prepared = get_problem(db, *id, &user)
+        .and_then(|problem| {
+            // Interactive solutions need the interactor to talk to.
+            if problem.interactive {
+                return Err(ErrorBadRequest("Interactive problems can't be run with custom input."));
+            }
+
+            let toolchain = language::table
+                .filter(language::id.eq(language))
+                .first::<Language>(db)
+                .optional()
+                .map_err(ErrorInternalServerError)?
+                .as_ref()
+                .and_then(Toolchain::for_language)
+                .ok_or_else(|| ErrorBadRequest("That language isn't accepting solutions."))?;
+
+            let limits = load_limits(db, &problem, language)
+                .map_err(ErrorInternalServerError)?;
+
+            state.runs.take(&user)
+                .map_err(ErrorBadRequest)?;
+
+            Ok((toolchain, limits))
+        });
+
+    let (toolchain, limits) = match prepared {
+        Ok(prepared) => prepared,
+        Err(err) => return Box::new(future::err(err)),
+    };
+
+    let resp = state.runner.run(db, toolchain, code, input, limits)
+        .map_err(|err| ErrorServiceUnavailable(err.to_string()))
+        .map(|run| {
+            let exec = run.execution;
+            HttpResponse::Ok().json(RunResult {
+                compile_error: run.compile_error,
+                status: exec.map(|exec| exec.describe()).unwrap_or_default(),
+                success: exec.map(|exec| exec.success()).unwrap_or(false),
+                time: exec.map(|exec| exec.time).unwrap_or(0),
+                memory: exec.map(|exec| exec.memory).unwrap_or(0),
+                stdout: run.stdout,
+                stderr: run.stderr,
+            })
+        });
+
+    Box::new(resp)
+}
+
+/// Sends the user back to the problem with an error.
+fn back(session: &Session, pid: i32, error: String) -> Result<HttpResponse> {
+    session.set("error", error)?;
+
+    Ok(HttpResponse::Found()
+        .header("location", format!("/problems/{}/", pid))
+        .finish())
+}
+
+/// Gets a problem the user can see, problems that haven't been released
+/// look like they don't exist.
+fn get_problem(db: &SqliteConnection, pid: i32, user: &User) -> Result<Problem> {
+    problem::table
+        .filter(problem::id.eq(pid))
+        .first::<Problem>(db)
+        .optional()
+        .map_err(ErrorInternalServerError)?
+        .filter(|problem| can_see_problem(Some(user), problem))
+        .ok_or_else(|| ErrorNotFound("No problem found."))
+}
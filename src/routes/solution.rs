@@ -0,0 +1,126 @@
+use actix_web::{
+    Result, App, Responder, HttpRequest, Path,
+    error::{ ErrorInternalServerError, ErrorNotFound },
+};
+use diesel::prelude::*;
+use liquid::{ Object, Value };
+
+use ::{
+    AppState,
+    diff,
+    db::{
+        models::{ Language, Problem, Solution, SolutionFile, TestResult, User },
+        schema::{ language, problem, queue, solution, solution_file, test_result, user },
+    },
+    middleware::Template,
+};
+
+pub fn configure(app: App<AppState>) -> App<AppState> {
+    app.resource("/solution/{id}/", |r| r.with(solution))
+}
+
+fn solution((req, id): (HttpRequest<AppState>, Path<i32>)) -> Result<impl Responder> {
+    let db = &req.state().db;
+    let current_user = req.extensions().get::<User>().cloned();
+
+    let (solution, problem, language, author) = solution::table
+        .inner_join(problem::table)
+        .inner_join(language::table)
+        .inner_join(user::table)
+        .filter(solution::id.eq(*id))
+        .first::<(Solution, Problem, Language, User)>(db)
+        .optional()
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorNotFound("No solution found."))?;
+
+    // Solutions that can't be viewed are treated as if they don't exist.
+    let visible = req.state().visibility
+        .can_view(db, current_user.as_ref(), &solution, &problem)
+        .map_err(ErrorInternalServerError)?;
+
+    if !visible {
+        return Err(ErrorNotFound("No solution found."));
+    }
+
+    let admin = current_user.as_ref().map(|user| user.admin).unwrap_or(false);
+    let results = test_result::table
+        .filter(test_result::sid.eq(solution.id))
+        .order(test_result::position.asc())
+        .load::<TestResult>(db)
+        .map_err(ErrorInternalServerError)?
+        .into_iter()
+        .map(|mut result| {
+            // What a solution wrote to stderr could give away a hidden test.
+            if result.hidden && !admin {
+                result.stderr.clear();
+            }
+
+            // Sample tests show how the output differs from what was expected.
+            let diff = match (&result.expected, &result.output) {
+                (&Some(ref expected), &Some(ref output)) => Some(diff::to_liquid(&diff::lines(expected, output))),
+                _ => None,
+            };
+
+            let mut obj = result.to_liquid();
+            if let Some(diff) = diff {
+                obj.insert("diff".into(), diff);
+            }
+            Value::Object(obj)
+        });
+
+    let mut files = solution_file::table
+        .filter(solution_file::sid.eq(solution.id))
+        .order(solution_file::name.asc())
+        .load::<SolutionFile>(db)
+        .map_err(ErrorInternalServerError)?;
+
+    // Solutions with a single file are shown as if it was uploaded on its
+    // own.
+    if files.is_empty() {
+        files.push(SolutionFile {
+            id: 0,
+            sid: solution.id,
+            name: language.source.clone(),
+            content: solution.code.clone(),
+        });
+    }
+
+    let files = files.into_iter()
+        .map(|file| {
+            let highlight = highlight(&file.name);
+            let mut obj = file.to_liquid();
+            obj.insert("highlight".into(), Value::scalar(highlight));
+            Value::Object(obj)
+        });
+
+    let queued = queue::table
+        .filter(queue::sid.eq(solution.id))
+        .count()
+        .get_result::<i64>(db)
+        .map_err(ErrorInternalServerError)?;
+
+    let mut obj = solution.to_liquid();
+    obj.remove("code");
+    obj.insert("files".into(), Value::array(files));
+    obj.insert("results".into(), Value::array(results));
+    obj.insert("queued".into(), Value::scalar(queued > 0));
+
+    let mut page = Object::new();
+    page.insert("solution".into(), Value::Object(obj));
+    page.insert("problem".into(), Value::Object(problem.to_liquid(true)));
+    page.insert("language".into(), Value::Object(language.to_liquid()));
+    page.insert("author".into(), Value::Object(author.to_liquid()));
+    Ok(Template::render("solution.liquid", page))
+}
+
+/// Guesses the highlight.js language from the extension of a file. It's
+/// used as a class, so extensions that aren't only letters and numbers are
+/// ignored.
+fn highlight(name: &str) -> String {
+    name.rsplit('.')
+        .next()
+        .filter(|ext| *ext != name)
+        .filter(|ext| ext.chars().all(|c| c.is_ascii_alphanumeric()))
+        .unwrap_or("")
+        .to_lowercase()
+}
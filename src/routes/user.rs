@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use actix_web::{
     Result,
     error::{
@@ -5,18 +7,20 @@ use actix_web::{
         ErrorInternalServerError,
         ErrorNotFound,
     },
-    App, Responder, State, Query, HttpResponse, Path,
+    App, Responder, State, Query, HttpRequest, HttpResponse, Path,
     middleware::session::Session,
 };
+use chrono::NaiveDateTime;
 use diesel::{ self, prelude::*, sql_types::Text };
 use liquid::{ Object, Value };
 
 use ::{
     AppState,
     db::{
-        models::User,
+        models::{ format_time, Problem, Solution, User },
         schema::{ oauth, user, problem, solution, language },
     },
+    judge::Verdict,
     oauth::GProfile,
     middleware::Template,
 };
@@ -109,7 +113,10 @@ fn logout(session: Session) -> impl Responder {
         .finish()
 }
 
-fn user((state, user): (State<AppState>, Path<String>)) -> Result<impl Responder> {
+fn user((req, user): (HttpRequest<AppState>, Path<String>)) -> Result<impl Responder> {
+    let state = req.state();
+    let current_user = req.extensions().get::<User>().cloned();
+
     let user = user::table
         .filter(user::sid.eq(user.as_str()))
         .first::<User>(&state.db)
@@ -121,20 +128,59 @@ fn user((state, user): (State<AppState>, Path<String>)) -> Result<impl Responder
         .filter(solution::uid.eq(user.id))
         .inner_join(language::table)
         .inner_join(problem::table)
-        .select((solution::id, problem::name, language::name))
-        .load::<(i32, String, String)>(&state.db)
-        .map_err(ErrorInternalServerError)?
-        .into_iter()
-        .map(|(id, name, language)| {
+        .select((solution::all_columns, problem::all_columns, language::name))
+        .order(solution::submitted_at.desc())
+        .load::<(Solution, Problem, String)>(&state.db)
+        .map_err(ErrorInternalServerError)?;
+
+    // Every attempt is kept, the problems list sums them up by problem with
+    // when the problem was first solved.
+    let mut problems = BTreeMap::<i32, (String, i32, Option<NaiveDateTime>)>::new();
+    for &(ref solution, ref problem, _) in &solutions {
+        let entry = problems.entry(problem.id).or_insert_with(|| (problem.name.clone(), 0, None));
+        entry.1 += 1;
+
+        if solution.verdict() == Verdict::Accepted {
+            entry.2 = Some(entry.2.map_or(solution.submitted_at, |first| first.min(solution.submitted_at)));
+        }
+    }
+
+    let solved = problems.values()
+        .filter(|&&(_, _, accepted)| accepted.is_some())
+        .count();
+
+    let problems = problems.into_iter()
+        .map(|(id, (name, attempts, accepted))| {
             let mut obj = Object::new();
             obj.insert("id".into(), Value::scalar(id));
             obj.insert("name".into(), Value::scalar(name));
-            obj.insert("language".into(), Value::scalar(language));
+            obj.insert("attempts".into(), Value::scalar(attempts));
+            if let Some(accepted) = accepted {
+                obj.insert("accepted_at".into(), Value::scalar(format_time(&accepted)));
+            }
             Value::Object(obj)
-        });
+        })
+        .collect::<Vec<_>>();
+
+    // Only solutions the current user is allowed to read are linked.
+    let mut list = Vec::new();
+    for (solution, problem, language) in solutions {
+        let visible = state.visibility
+            .can_view(&state.db, current_user.as_ref(), &solution, &problem)
+            .map_err(ErrorInternalServerError)?;
+
+        let mut obj = solution.to_liquid();
+        obj.remove("code");
+        obj.insert("name".into(), Value::scalar(problem.name));
+        obj.insert("language".into(), Value::scalar(language));
+        obj.insert("visible".into(), Value::scalar(visible));
+        list.push(Value::Object(obj));
+    }
 
     let mut user = user.to_liquid();
-    user.insert("solutions".into(), Value::array(solutions));
+    user.insert("problems".into(), Value::array(problems));
+    user.insert("solutions".into(), Value::array(list));
+    user.insert("solved".into(), Value::scalar(solved as i32));
 
     let mut obj = Object::new();
     obj.insert("user".into(), Value::Object(user));
@@ -0,0 +1,149 @@
+//! Tags group problems by topic, such as graphs or dp. Names are kept in
+//! lowercase and tags are removed once no problem has them.
+
+use std::collections::HashMap;
+
+use diesel::{ self, prelude::*, dsl::not };
+
+use ::db::schema::{ problem_tag, tag };
+
+/// The most tags a problem can have.
+pub const MAX_TAGS: usize = 10;
+
+/// The longest a tag's name can be.
+const MAX_LENGTH: usize = 32;
+
+/// Reads a comma separated list of tags, ignoring duplicates.
+pub fn parse(input: &str) -> Result<Vec<String>, String> {
+    let mut tags = Vec::new();
+
+    for name in input.split(',').map(|name| name.trim().to_lowercase()) {
+        if name.is_empty() || tags.contains(&name) {
+            continue;
+        }
+
+        if name.chars().count() > MAX_LENGTH {
+            return Err(format!("Tags can't be longer than {} characters.", MAX_LENGTH));
+        }
+        if !name.chars().all(|c| c.is_alphanumeric() || c == ' ' || c == '-') {
+            return Err(format!("\"{}\" isn't a valid tag, tags can only have letters, numbers, spaces and dashes.", name));
+        }
+
+        tags.push(name);
+    }
+
+    if tags.len() > MAX_TAGS {
+        return Err(format!("Problems can't have more than {} tags.", MAX_TAGS));
+    }
+
+    Ok(tags)
+}
+
+/// Loads the tags of every problem by the problem's id, sorted by name.
+pub fn load(db: &SqliteConnection) -> QueryResult<HashMap<i32, Vec<String>>> {
+    let rows = problem_tag::table
+        .inner_join(tag::table)
+        .select((problem_tag::pid, tag::name))
+        .order(tag::name.asc())
+        .load::<(i32, String)>(db)?;
+
+    let mut tags = HashMap::<i32, Vec<String>>::new();
+    for (pid, name) in rows {
+        tags.entry(pid).or_insert_with(Vec::new).push(name);
+    }
+
+    Ok(tags)
+}
+
+/// Loads the tags of a problem, sorted by name.
+pub fn for_problem(db: &SqliteConnection, pid: i32) -> QueryResult<Vec<String>> {
+    problem_tag::table
+        .inner_join(tag::table)
+        .filter(problem_tag::pid.eq(pid))
+        .select(tag::name)
+        .order(tag::name.asc())
+        .load::<String>(db)
+}
+
+/// Gives a problem exactly these tags, creating the ones that don't exist.
+pub fn save(db: &SqliteConnection, pid: i32, tags: &[String]) -> QueryResult<()> {
+    diesel::delete(problem_tag::table.filter(problem_tag::pid.eq(pid)))
+        .execute(db)?;
+
+    for name in tags {
+        let existing = tag::table
+            .filter(tag::name.eq(name))
+            .select(tag::id)
+            .first::<i32>(db)
+            .optional()?;
+
+        let tid = match existing {
+            Some(tid) => tid,
+            None => {
+                diesel::insert_into(tag::table)
+                    .values(tag::name.eq(name))
+                    .execute(db)?;
+
+                tag::table
+                    .filter(tag::name.eq(name))
+                    .select(tag::id)
+                    .first::<i32>(db)?
+            }
+        };
+
+        diesel::insert_into(problem_tag::table)
+            .values((
+                problem_tag::pid.eq(pid),
+                problem_tag::tid.eq(tid),
+            ))
+            .execute(db)?;
+    }
+
+    prune(db)
+}
+
+/// Removes the tags that no problem has anymore.
+pub fn prune(db: &SqliteConnection) -> QueryResult<()> {
+    diesel::delete(tag::table.filter(not(tag::id.eq_any(problem_tag::table.select(problem_tag::tid)))))
+        .execute(db)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_skips_duplicates_and_empty_names() {
+        assert_eq!(parse("graphs, DP,,Graphs , dp,").unwrap(), vec!["graphs", "dp"]);
+        assert_eq!(parse("").unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn parse_rejects_long_names() {
+        let longest = "a".repeat(MAX_LENGTH);
+        assert_eq!(parse(&longest).unwrap(), vec![longest.clone()]);
+        assert!(parse(&format!("{}a", longest)).is_err());
+
+        // The length is counted in characters, not bytes.
+        let accented = "é".repeat(MAX_LENGTH);
+        assert_eq!(parse(&accented).unwrap(), vec![accented.clone()]);
+    }
+
+    #[test]
+    fn parse_rejects_too_many_tags() {
+        let names = (0..MAX_TAGS).map(|i| format!("tag {}", i)).collect::<Vec<_>>();
+        assert_eq!(parse(&names.join(",")).unwrap().len(), MAX_TAGS);
+        assert!(parse(&format!("{},one more", names.join(","))).is_err());
+
+        // Duplicates don't count towards the limit.
+        assert!(parse(&format!("{},tag 0", names.join(","))).is_ok());
+    }
+
+    #[test]
+    fn parse_rejects_other_characters() {
+        assert!(parse("graphs; dp").is_err());
+        assert!(parse("<b>").is_err());
+    }
+}
@@ -0,0 +1,102 @@
+//! Decides who can read the code of a solution. Authors can always read
+//! their own code and admins can read everything, whether anyone else can
+//! depends on the policy.
+
+use std::env;
+
+use chrono::Utc;
+use diesel::prelude::*;
+
+use ::{
+    db::{
+        models::{ Problem, ProblemState, Solution, User },
+        schema::solution,
+    },
+    judge::Verdict,
+};
+
+#[derive(Clone, Debug)]
+pub struct Policy {
+    /// Accepted solutions can be read by anyone once their problem is due.
+    pub after_deadline: bool,
+    /// Accepted solutions can be read by anyone who has solved the problem.
+    pub after_solving: bool,
+}
+
+impl Policy {
+    /// Reads the policy from the environment, both rules are on unless
+    /// they're turned off.
+    pub fn from_env() -> Self {
+        let flag = |name: &str| env::var(name)
+            .map(|value| value != "0" && value != "false")
+            .unwrap_or(true);
+
+        Policy {
+            after_deadline: flag("POTW_SHOW_AFTER_DEADLINE"),
+            after_solving: flag("POTW_SHOW_AFTER_SOLVING"),
+        }
+    }
+
+    /// Checks if the viewer can read the code of a solution to the problem.
+    pub fn can_view(&self, db: &SqliteConnection, viewer: Option<&User>, solution: &Solution, problem: &Problem) -> QueryResult<bool> {
+        let viewer = match viewer {
+            Some(viewer) => viewer,
+            None => return Ok(false),
+        };
+
+        if viewer.admin || viewer.id == solution.uid {
+            return Ok(true);
+        }
+
+        if solution.verdict() != Verdict::Accepted {
+            return Ok(false);
+        }
+
+        if self.after_deadline && is_due(problem) {
+            return Ok(true);
+        }
+
+        if self.after_solving {
+            return has_solved(db, viewer.id, problem.id);
+        }
+
+        Ok(false)
+    }
+}
+
+/// Checks if the problem's deadline has passed.
+pub fn is_due(problem: &Problem) -> bool {
+    problem.due_at
+        .map(|due_at| due_at <= Utc::now().naive_utc())
+        .unwrap_or(false)
+}
+
+/// Checks if the problem's release date has passed, problems without one
+/// are always released.
+pub fn is_released(problem: &Problem) -> bool {
+    problem.release_at
+        .map(|release_at| release_at <= Utc::now().naive_utc())
+        .unwrap_or(true)
+}
+
+/// Checks if the viewer can see a problem, only admins can see drafts and
+/// problems that haven't been released.
+pub fn can_see_problem(viewer: Option<&User>, problem: &Problem) -> bool {
+    if viewer.map(|viewer| viewer.admin).unwrap_or(false) {
+        return true;
+    }
+
+    problem.state() != ProblemState::Draft && is_released(problem)
+}
+
+/// Checks if the user has an accepted solution to the problem.
+pub fn has_solved(db: &SqliteConnection, uid: i32, pid: i32) -> QueryResult<bool> {
+    let accepted = solution::table
+        .filter(solution::uid.eq(uid))
+        .filter(solution::pid.eq(pid))
+        .filter(solution::verdict.eq(Verdict::Accepted.code()))
+        .count()
+        .get_result::<i64>(db)?;
+
+    Ok(accepted > 0)
+}